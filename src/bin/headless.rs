@@ -0,0 +1,61 @@
+use ash_raytracing::{
+    renderer::film::Film,
+    renderer::render_backend,
+    renderer::settings::{self, RenderArgs},
+    renderer::thread_pool,
+    renderer::tile_scheduler,
+};
+
+use std::env;
+use std::process;
+use std::time::Instant;
+use std::{sync::{Arc, Mutex}};
+
+// Renders one frame on the CPU without creating a window or touching Vulkan,
+// then writes it to disk and exits.
+//
+// usage: headless [OPTIONS] [OUTPUT]      (default: built-in scene, output.png)
+fn main() {
+    let mut args = match RenderArgs::parse(env::args().skip(1))
+    {
+        Ok(args) => args,
+        Err(err) =>
+        {
+            eprintln!("{}\nusage: headless {}", err, settings::USAGE);
+            process::exit(2);
+        }
+    };
+    // A bare argument is the output file, as before --output existed
+    if args.output.is_none()
+    {
+        args.output = args.positional.take();
+    }
+
+    let start = Instant::now();
+
+    let (scene, settings) = match render_backend::load_or_build_scene(args.scene.as_deref(), &args)
+    {
+        Ok((scene, settings)) => (Arc::new(scene), settings),
+        Err(err) =>
+        {
+            eprintln!("{}: {}", args.scene.unwrap_or_default().display(), err);
+            process::exit(1);
+        }
+    };
+
+    let film = Arc::new(Mutex::new(Film::new(settings.width, settings.height)));
+    let thread_pool = thread_pool::ThreadPool::new(settings.threads);
+
+    tile_scheduler::render_tiles(&thread_pool, &scene, &film, settings.tile_size, settings.tile_order);
+
+    // Dropping the pool waits for every queued tile to finish
+    drop(thread_pool);
+
+    if let Err(err) = film.lock().unwrap().write(&settings.output)
+    {
+        eprintln!("Failed to write {}: {}", settings.output.display(), err);
+        process::exit(1);
+    }
+
+    println!("Rendered {}x{} at {} spp in {:.2?} -> {}", settings.width, settings.height, settings.samples_per_pixel, start.elapsed(), settings.output.display());
+}
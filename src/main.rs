@@ -7,17 +7,22 @@ use ash_raytracing::{
     utility::window::{ProgramProc, VulkanApp},
 
     //renderer,
+    renderer::film::Film,
     renderer::render_backend,
+    renderer::settings::{self, RenderArgs},
     renderer::thread_pool,
+    renderer::tile_scheduler,
 };
 
 use ash::version::DeviceV1_0;
 use ash::version::InstanceV1_0;
 use ash::vk;
-use cgmath::{Array, Deg, Matrix4, Point3, SquareMatrix, Vector3, Vector4};
+use cgmath::{Deg, Matrix4, Point3, SquareMatrix, Vector3, Vector4};
 
+use std::env;
 use std::ffi::CString;
 use std::mem::size_of;
+use std::process;
 use std::ptr;
 use std::{sync::{Arc, Mutex}};
 
@@ -1154,27 +1159,46 @@ impl VulkanApp for RayTracing {
 }
 
 fn main() {
-    let program_proc = ProgramProc::new();
-    let vulkan_app = RayTracing::new(&program_proc.event_loop);
-
-    // RenderData： 开启多线程渲染
-    let data_size = WINDOW_WIDTH * WINDOW_HEIGHT;
-    let render_data = Arc::new(Mutex::new(vec![Vector4::from_value(1.0); data_size as usize]));
+    // usage: ash_raytracing [OPTIONS] [SCENE]      (default: built-in scene)
+    let mut args = match RenderArgs::parse(env::args().skip(1))
+    {
+        Ok(args) => args,
+        Err(err) =>
+        {
+            eprintln!("{}\nusage: ash_raytracing {}", err, settings::USAGE);
+            process::exit(2);
+        }
+    };
+    let scene_path = args.scene.clone().or_else(|| args.positional.clone());
 
-    let thread_pool = thread_pool::ThreadPool::new(6);
+    // The preview shader has the window size built in
+    if args.width.is_some_and(|w| w != WINDOW_WIDTH) || args.height.is_some_and(|h| h != WINDOW_HEIGHT)
+    {
+        eprintln!("The preview window is always {}x{}, use the headless binary for other resolutions", WINDOW_WIDTH, WINDOW_HEIGHT);
+    }
+    args.width = Some(WINDOW_WIDTH);
+    args.height = Some(WINDOW_HEIGHT);
 
-    for j in 0..WINDOW_HEIGHT
+    let (scene, settings) = match render_backend::load_or_build_scene(scene_path.as_deref(), &args)
     {
-        for i in 0..WINDOW_WIDTH
+        Ok((scene, settings)) => (Arc::new(scene), settings),
+        Err(err) =>
         {
-            let local_array = Arc::clone(&render_data);
-            thread_pool.exec(Box::new(move || {
-                let mut array = local_array.lock().unwrap();
-                array[(i.clone() + j.clone() * WINDOW_WIDTH) as usize] = render_backend::render(WINDOW_WIDTH, WINDOW_HEIGHT, i.clone(), j.clone());
-            }));
+            eprintln!("{}: {}", scene_path.unwrap_or_default().display(), err);
+            process::exit(1);
         }
-    }
+    };
+
+    let program_proc = ProgramProc::new();
+    let vulkan_app = RayTracing::new(&program_proc.event_loop);
+
+    // Film： 开启多线程渲染, 每一遍给所有像素追加 SAMPLES_PER_PASS 个采样
+    let film = Arc::new(Mutex::new(Film::new(settings.width, settings.height)));
+
+    let thread_pool = thread_pool::ThreadPool::new(settings.threads);
+
+    tile_scheduler::render_progressive(&thread_pool, &scene, &film, settings.tile_size, settings.tile_order, SAMPLES_PER_PASS);
 
-    program_proc.main_loop(vulkan_app, render_data);
+    program_proc.main_loop(vulkan_app, film);
 }
 // -------------------------------------------------------------------------------------------
\ No newline at end of file
@@ -0,0 +1,147 @@
+use cgmath::Vector3;
+use crate::renderer::interval::Interval;
+use crate::renderer::ray::Ray;
+
+// Smallest extent a box may have along any axis, keeps flat primitives hittable
+const MIN_EXTENT: f32 = 0.0001;
+
+#[derive(Copy, Clone, Debug)]
+pub struct Aabb
+{
+    _x: Interval,
+    _y: Interval,
+    _z: Interval
+}
+
+impl Aabb
+{
+    pub fn new(x: Interval, y: Interval, z: Interval) -> Self
+    {
+        let mut bbox = Aabb { _x: x, _y: y, _z: z };
+        bbox.pad_to_minimums();
+        bbox
+    }
+
+    // Treat the two points `a` and `b` as extrema for the bounding box
+    pub fn from_points(a: Vector3<f32>, b: Vector3<f32>) -> Self
+    {
+        Aabb::new(Interval::new(a.x.min(b.x), a.x.max(b.x)),
+                  Interval::new(a.y.min(b.y), a.y.max(b.y)),
+                  Interval::new(a.z.min(b.z), a.z.max(b.z)))
+    }
+
+    pub fn surrounding(box0: &Aabb, box1: &Aabb) -> Self
+    {
+        Aabb
+        {
+            _x: Interval::enclosing(box0._x, box1._x),
+            _y: Interval::enclosing(box0._y, box1._y),
+            _z: Interval::enclosing(box0._z, box1._z)
+        }
+    }
+
+    pub fn empty() -> Self
+    {
+        Aabb { _x: Interval::empty(), _y: Interval::empty(), _z: Interval::empty() }
+    }
+
+    pub fn axis_interval(&self, n: usize) -> Interval
+    {
+        match n
+        {
+            1 => self._y,
+            2 => self._z,
+            _ => self._x
+        }
+    }
+
+    pub fn min(&self) -> Vector3<f32>
+    {
+        Vector3::new(self._x.min(), self._y.min(), self._z.min())
+    }
+
+    pub fn max(&self) -> Vector3<f32>
+    {
+        Vector3::new(self._x.max(), self._y.max(), self._z.max())
+    }
+
+    pub fn centroid(&self) -> Vector3<f32>
+    {
+        (self.min() + self.max()) * 0.5
+    }
+
+    // Index of the axis with the largest extent
+    pub fn longest_axis(&self) -> usize
+    {
+        if self._x.size() > self._y.size()
+        {
+            if self._x.size() > self._z.size() { 0 } else { 2 }
+        }
+        else
+        {
+            if self._y.size() > self._z.size() { 1 } else { 2 }
+        }
+    }
+
+    pub fn surface_area(&self) -> f32
+    {
+        let (dx, dy, dz) = (self._x.size(), self._y.size(), self._z.size());
+        if dx < 0.0 || dy < 0.0 || dz < 0.0
+        {
+            return 0.0;
+        }
+        2.0 * (dx * dy + dy * dz + dz * dx)
+    }
+
+    // Slab test against the three axis intervals
+    pub fn hit(&self, r: &Ray, ray_t: Interval) -> bool
+    {
+        let ray_orig = r.origin();
+        let ray_dir = r.direction();
+
+        let mut t_min = ray_t.min();
+        let mut t_max = ray_t.max();
+
+        for axis in 0..3
+        {
+            let ax = self.axis_interval(axis);
+            let adinv = 1.0 / ray_dir[axis];
+
+            let t0 = (ax.min() - ray_orig[axis]) * adinv;
+            let t1 = (ax.max() - ray_orig[axis]) * adinv;
+
+            let (near, far) = if t0 < t1 { (t0, t1) } else { (t1, t0) };
+            if near > t_min
+            {
+                t_min = near;
+            }
+            if far < t_max
+            {
+                t_max = far;
+            }
+
+            if t_max <= t_min
+            {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    fn pad_to_minimums(&mut self)
+    {
+        if self._x.size() < MIN_EXTENT
+        {
+            self._x = self._x.expand(MIN_EXTENT);
+        }
+        if self._y.size() < MIN_EXTENT
+        {
+            self._y = self._y.expand(MIN_EXTENT);
+        }
+        if self._z.size() < MIN_EXTENT
+        {
+            self._z = self._z.expand(MIN_EXTENT);
+        }
+    }
+}
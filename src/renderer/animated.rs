@@ -0,0 +1,87 @@
+use std::sync::Arc;
+use cgmath::Vector3;
+use crate::renderer::aabb::Aabb;
+use crate::renderer::hittable::{HitRecord, Hittable};
+use crate::renderer::interval::Interval;
+use crate::renderer::ray::Ray;
+
+// Slides any hittable along a straight line, by `offset0` at time 0 and by
+// `offset1` at time 1. Rays are moved into the object's frame at their own
+// time instead of moving the object, so meshes blur without being rebuilt.
+pub struct AnimatedTranslate
+{
+    _object: Arc<dyn Hittable>,
+    _offset0: Vector3<f32>,
+    _offset1: Vector3<f32>,
+    _bbox: Aabb
+}
+
+impl AnimatedTranslate
+{
+    pub fn new(object: Arc<dyn Hittable>, offset0: Vector3<f32>, offset1: Vector3<f32>) -> Self
+    {
+        let bbox = object.bounding_box();
+        let box0 = Aabb::from_points(bbox.min() + offset0, bbox.max() + offset0);
+        let box1 = Aabb::from_points(bbox.min() + offset1, bbox.max() + offset1);
+        AnimatedTranslate
+        {
+            _object: object,
+            _offset0: offset0,
+            _offset1: offset1,
+            _bbox: Aabb::surrounding(&box0, &box1)
+        }
+    }
+
+    fn offset(&self, time: f32) -> Vector3<f32>
+    {
+        self._offset0 + time * (self._offset1 - self._offset0)
+    }
+}
+
+impl Hittable for AnimatedTranslate
+{
+    fn hit(&self, ray: Ray, ray_t: Interval) -> Option<HitRecord>
+    {
+        let offset = self.offset(ray.time());
+        let moved = Ray::with_time(ray.origin() - offset, ray.direction(), ray.time());
+
+        let mut rec = self._object.hit(moved, ray_t)?;
+        rec._point += offset;
+        Some(rec)
+    }
+
+    fn bounding_box(&self) -> Aabb
+    {
+        self._bbox
+    }
+}
+
+#[cfg(test)]
+mod test
+{
+    use cgmath::Array;
+    use super::*;
+    use crate::renderer::material::Lambertian;
+    use crate::renderer::sphere::Sphere;
+
+    #[test]
+    fn test_position_follows_ray_time()
+    {
+        let material = Arc::new(Lambertian::new(Vector3::from_value(0.5)));
+        let sphere = Arc::new(Sphere::new(Vector3::new(0.0, 0.0, 0.0), 0.5, material.clone()));
+        let animated = AnimatedTranslate::new(sphere, Vector3::new(0.0, 0.0, 0.0), Vector3::new(2.0, 0.0, 0.0));
+        let moving = Sphere::moving(Vector3::new(0.0, 0.0, 0.0), Vector3::new(2.0, 0.0, 0.0), 0.5, material);
+
+        for object in &[&animated as &dyn Hittable, &moving as &dyn Hittable]
+        {
+            // Straight down at x = 2: misses at the start of the shutter, hits at the end
+            let at = |time| Ray::with_time(Vector3::new(2.0, 5.0, 0.0), Vector3::new(0.0, -1.0, 0.0), time);
+            assert!(object.hit(at(0.0), Interval::new(0.001, f32::INFINITY)).is_none());
+            let hit = object.hit(at(1.0), Interval::new(0.001, f32::INFINITY)).unwrap();
+            assert!((hit.get_point() - Vector3::new(2.0, 0.5, 0.0)).x.abs() < 1e-5);
+            assert!((hit.get_t() - 4.5).abs() < 1e-5);
+
+            assert_eq!(object.bounding_box().axis_interval(0).max(), 2.5);
+        }
+    }
+}
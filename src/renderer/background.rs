@@ -0,0 +1,204 @@
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use cgmath::Vector3;
+use crate::renderer::custom_function::{degrees_to_radians, srgb_to_linear, unit_vector3};
+use crate::renderer::film;
+use crate::utility::constants::PI;
+
+// Radiance arriving from infinitely far away along a direction that left the scene
+pub trait Background: Sync + Send
+{
+    fn value(&self, direction: Vector3<f32>) -> Vector3<f32>;
+}
+
+pub struct SolidBackground
+{
+    color: Vector3<f32>
+}
+
+impl SolidBackground
+{
+    pub fn new(color: Vector3<f32>) -> Self
+    {
+        SolidBackground
+        {
+            color
+        }
+    }
+}
+
+impl Background for SolidBackground
+{
+    fn value(&self, _direction: Vector3<f32>) -> Vector3<f32>
+    {
+        self.color
+    }
+}
+
+// Vertical blend from `bottom` (looking straight down) to `top` (straight up)
+pub struct GradientBackground
+{
+    bottom: Vector3<f32>,
+    top: Vector3<f32>
+}
+
+impl GradientBackground
+{
+    pub fn new(bottom: Vector3<f32>, top: Vector3<f32>) -> Self
+    {
+        GradientBackground
+        {
+            bottom,
+            top
+        }
+    }
+
+    // The white to light blue sky used by the renderer by default
+    pub fn sky() -> Self
+    {
+        GradientBackground::new(Vector3::new(1.0, 1.0, 1.0), Vector3::new(0.5, 0.7, 1.0))
+    }
+}
+
+impl Background for GradientBackground
+{
+    fn value(&self, direction: Vector3<f32>) -> Vector3<f32>
+    {
+        let unit_dir = unit_vector3(direction);
+        let a = 0.5 * (unit_dir.y + 1.0);
+        (1.0 - a) * self.bottom + a * self.top
+    }
+}
+
+// Equirectangular (latitude/longitude) environment map
+pub struct EnvironmentMap
+{
+    width: usize,
+    height: usize,
+    pixels: Vec<Vector3<f32>>,
+    rotation: f32,
+    intensity: f32
+}
+
+impl EnvironmentMap
+{
+    // `rotation` turns the map around the world up axis, in degrees
+    pub fn new(width: usize, height: usize, pixels: Vec<Vector3<f32>>, rotation: f32, intensity: f32) -> Self
+    {
+        assert_eq!(pixels.len(), width * height, "environment map size does not match its pixel count");
+        EnvironmentMap
+        {
+            width,
+            height,
+            pixels,
+            rotation: degrees_to_radians(rotation),
+            intensity
+        }
+    }
+
+    // Radiance .hdr and OpenEXR .exr files are read as linear floats, any other
+    // format the image crate understands is treated as sRGB and linearised
+    pub fn load(path: &Path, rotation: f32, intensity: f32) -> Result<Self, String>
+    {
+        let has_extension = |name: &str| path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case(name));
+
+        let (width, height, pixels) = if has_extension("exr")
+        {
+            let (w, h, data) = film::read_exr(path).map_err(|err| format!("failed to read {}: {}", path.display(), err))?;
+            let pixels = data.iter().map(|p| Vector3::new(p.x, p.y, p.z)).collect();
+            (w as usize, h as usize, pixels)
+        }
+        else if has_extension("hdr")
+        {
+            let file = File::open(path).map_err(|err| format!("failed to open {}: {}", path.display(), err))?;
+            let decoder = image::hdr::HDRDecoder::new(BufReader::new(file)).map_err(|err| format!("failed to read {}: {}", path.display(), err))?;
+            let meta = decoder.metadata();
+            let data = decoder.read_image_hdr().map_err(|err| format!("failed to read {}: {}", path.display(), err))?;
+            let pixels = data.iter().map(|p| Vector3::new(p.0[0], p.0[1], p.0[2])).collect();
+            (meta.width as usize, meta.height as usize, pixels)
+        }
+        else
+        {
+            let img = image::open(path).map_err(|err| format!("failed to read {}: {}", path.display(), err))?.to_rgb();
+            let (w, h) = img.dimensions();
+            let pixels = img.pixels().map(|p| Vector3::new(srgb_to_linear(p.0[0]), srgb_to_linear(p.0[1]), srgb_to_linear(p.0[2]))).collect();
+            (w as usize, h as usize, pixels)
+        };
+
+        if width == 0 || height == 0
+        {
+            return Err(format!("{} is empty", path.display()));
+        }
+
+        Ok(EnvironmentMap::new(width, height, pixels, rotation, intensity))
+    }
+}
+
+impl Background for EnvironmentMap
+{
+    fn value(&self, direction: Vector3<f32>) -> Vector3<f32>
+    {
+        let d = unit_vector3(direction);
+
+        // -z is the centre of the map, +y the top row
+        let phi = d.x.atan2(-d.z) + self.rotation;
+        let theta = d.y.clamp(-1.0, 1.0).acos();
+
+        let u = (phi / (2.0 * PI) + 0.5).rem_euclid(1.0);
+        let v = theta / PI;
+
+        let x = ((u * self.width as f32) as usize).min(self.width - 1);
+        let y = ((v * self.height as f32) as usize).min(self.height - 1);
+
+        self.intensity * self.pixels[x + y * self.width]
+    }
+}
+
+#[cfg(test)]
+mod test
+{
+    use cgmath::Vector4;
+    use super::*;
+
+    #[test]
+    fn test_gradient_ends()
+    {
+        let sky = GradientBackground::sky();
+        assert_eq!(sky.value(Vector3::new(0.0, 1.0, 0.0)), Vector3::new(0.5, 0.7, 1.0));
+        assert_eq!(sky.value(Vector3::new(0.0, -1.0, 0.0)), Vector3::new(1.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn test_environment_map_lookup()
+    {
+        // Column 2 (u = 0.5) is the one seen looking down -z
+        let black = Vector3::new(0.0, 0.0, 0.0);
+        let red = Vector3::new(1.0, 0.0, 0.0);
+        let pixels = vec![black, black, red, black];
+        let forward = Vector3::new(0.0, 0.0, -1.0);
+
+        let envmap = EnvironmentMap::new(4, 1, pixels.clone(), 0.0, 2.0);
+        assert_eq!(envmap.value(forward), Vector3::new(2.0, 0.0, 0.0));
+
+        // A quarter turn moves the red column away from -z
+        let rotated = EnvironmentMap::new(4, 1, pixels, 90.0, 1.0);
+        assert_eq!(rotated.value(forward), black);
+        assert_eq!(rotated.value(Vector3::new(-1.0, 0.0, 0.0)), red);
+    }
+
+    #[test]
+    fn test_environment_map_from_exr()
+    {
+        // Values above 1 must survive, .exr maps are linear radiance
+        let mut film = film::Film::new(2, 1);
+        film.add_samples(0, 0, 2, &[Vector4::new(4.0, 0.5, 0.25, 1.0), Vector4::new(0.0, 1.0, 8.0, 1.0)], 1);
+        let path = std::env::temp_dir().join("background_test_environment_map.exr");
+        film.write(&path).unwrap();
+
+        let envmap = EnvironmentMap::load(&path, 0.0, 1.0);
+        std::fs::remove_file(&path).unwrap();
+        let envmap = envmap.unwrap();
+        assert_eq!(envmap.pixels, vec![Vector3::new(4.0, 0.5, 0.25), Vector3::new(0.0, 1.0, 8.0)]);
+    }
+}
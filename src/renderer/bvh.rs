@@ -0,0 +1,168 @@
+use std::sync::Arc;
+use crate::renderer::aabb::Aabb;
+use crate::renderer::hittable::{HitRecord, Hittable};
+use crate::renderer::hittable_list::HittableList;
+use crate::renderer::interval::Interval;
+use crate::renderer::ray::Ray;
+
+pub struct BvhNode
+{
+    _left: Arc<dyn Hittable>,
+    _right: Arc<dyn Hittable>,
+    _bbox: Aabb
+}
+
+impl BvhNode
+{
+    pub fn new(list: HittableList) -> Self
+    {
+        let mut objects = list.objects.clone();
+        BvhNode::from_objects(&mut objects)
+    }
+
+    // Splits along the longest axis of the centroid bounds, choosing the partition
+    // with the lowest surface area heuristic cost
+    pub fn from_objects(objects: &mut [Arc<dyn Hittable>]) -> Self
+    {
+        assert!(!objects.is_empty(), "cannot build a BVH from an empty object list");
+
+        let mut bbox = Aabb::empty();
+        let mut centroid_bounds = Aabb::empty();
+        for object in objects.iter()
+        {
+            bbox = Aabb::surrounding(&bbox, &object.bounding_box());
+            let c = object.bounding_box().centroid();
+            centroid_bounds = Aabb::surrounding(&centroid_bounds, &Aabb::from_points(c, c));
+        }
+
+        match objects.len()
+        {
+            1 =>
+            {
+                let only = objects[0].clone();
+                return BvhNode { _left: only.clone(), _right: only, _bbox: bbox };
+            }
+            2 =>
+            {
+                return BvhNode { _left: objects[0].clone(), _right: objects[1].clone(), _bbox: bbox };
+            }
+            _ => {}
+        }
+
+        let axis = centroid_bounds.longest_axis();
+        objects.sort_by(|a, b| {
+            let ca = a.bounding_box().centroid()[axis];
+            let cb = b.bounding_box().centroid()[axis];
+            ca.partial_cmp(&cb).unwrap_or(std::cmp::Ordering::Equal)
+        });
+
+        let mid = BvhNode::sah_split(objects);
+        let (left_objects, right_objects) = objects.split_at_mut(mid);
+
+        BvhNode
+        {
+            _left: BvhNode::child(left_objects),
+            _right: BvhNode::child(right_objects),
+            _bbox: bbox
+        }
+    }
+
+    fn child(objects: &mut [Arc<dyn Hittable>]) -> Arc<dyn Hittable>
+    {
+        if objects.len() == 1
+        {
+            objects[0].clone()
+        }
+        else
+        {
+            Arc::new(BvhNode::from_objects(objects))
+        }
+    }
+
+    // Index of the first object of the right half, `objects` must be sorted along the split axis
+    fn sah_split(objects: &[Arc<dyn Hittable>]) -> usize
+    {
+        let n = objects.len();
+
+        // right_area[i] is the surface area of the box around objects[i..]
+        let mut right_area = vec![0.0; n];
+        let mut right_box = Aabb::empty();
+        for i in (1..n).rev()
+        {
+            right_box = Aabb::surrounding(&right_box, &objects[i].bounding_box());
+            right_area[i] = right_box.surface_area();
+        }
+
+        let mut best_cost = f32::INFINITY;
+        let mut best_split = n / 2;
+        let mut left_box = Aabb::empty();
+        for i in 1..n
+        {
+            left_box = Aabb::surrounding(&left_box, &objects[i - 1].bounding_box());
+            let cost = left_box.surface_area() * i as f32 + right_area[i] * (n - i) as f32;
+            if cost < best_cost
+            {
+                best_cost = cost;
+                best_split = i;
+            }
+        }
+
+        best_split
+    }
+}
+
+impl Hittable for BvhNode
+{
+    fn hit(&self, ray: Ray, ray_t: Interval) -> Option<HitRecord>
+    {
+        if !self._bbox.hit(&ray, ray_t)
+        {
+            return None;
+        }
+
+        let hit_left = self._left.hit(ray, ray_t);
+        let right_max = match &hit_left
+        {
+            Some(rec) => rec._t,
+            None => ray_t.max()
+        };
+        let hit_right = self._right.hit(ray, Interval::new(ray_t.min(), right_max));
+
+        hit_right.or(hit_left)
+    }
+
+    fn bounding_box(&self) -> Aabb
+    {
+        self._bbox
+    }
+}
+
+#[cfg(test)]
+mod test
+{
+    use cgmath::Vector3;
+    use crate::renderer::custom_function::{random_double_range, random_unit_vector3, random_vector3_range};
+    use crate::renderer::material::Lambertian;
+    use crate::renderer::sphere::Sphere;
+    use super::*;
+
+    #[test]
+    fn test_bvh_matches_linear_list()
+    {
+        let material = Arc::new(Lambertian::new(Vector3::new(0.5, 0.5, 0.5)));
+        let mut list = HittableList::new();
+        for _ in 0..200
+        {
+            list.add(Arc::new(Sphere::new(random_vector3_range(-10.0, 10.0), random_double_range(0.1, 1.0), material.clone())));
+        }
+        let bvh = BvhNode::new(list.clone());
+
+        for _ in 0..1000
+        {
+            let r = Ray::new(random_vector3_range(-15.0, 15.0), random_unit_vector3());
+            let linear = list.hit(r, Interval::new(0.001, f32::INFINITY)).map(|h| h._t);
+            let tree = bvh.hit(r, Interval::new(0.001, f32::INFINITY)).map(|h| h._t);
+            assert_eq!(linear, tree);
+        }
+    }
+}
@@ -1,77 +1,270 @@
-use cgmath::{Vector3, Vector4, Array, InnerSpace};
-use crate::renderer::custom_function::{degrees_to_radians, length, linear_to_gamma, random_double, random_in_unit_disk, random_on_hemisphere, random_unit_vector3, unit_vector3};
+use std::ops::Range;
+use std::sync::Arc;
+use cgmath::{dot, Vector3, Vector4, Array, ElementWise, InnerSpace};
+use crate::renderer::background::Background;
+use crate::renderer::film::resolve_color;
+use crate::renderer::custom_function::{degrees_to_radians, length, random_double, random_in_unit_disk, random_on_hemisphere, random_unit_vector3, unit_vector3};
 use crate::renderer::hittable::{HitRecord, Hittable};
-use crate::renderer::hittable_list::HittableList;
 use crate::renderer::interval::Interval;
+use crate::renderer::ior_stack::IorStack;
 use crate::renderer::material::Material;
+use crate::renderer::pdf::{power_heuristic, HittablePdf, Pdf};
 use crate::renderer::ray::Ray;
-use crate::utility::constants::{INFINITY, MAX_DEPTH, SAMPLES_PER_PIXEL};
+use crate::renderer::sampler::seed_thread_sampler;
+use crate::renderer::scene::SceneView;
+use crate::renderer::settings::RenderSettings;
+use crate::renderer::spectrum::{PathColor, SampledWavelengths};
+use crate::utility::constants::{INFINITY, MAX_DEPTH, ROULETTE_DEPTH, SAMPLES_PER_PIXEL, WINDOW_HEIGHT, WINDOW_WIDTH};
 
 
+// Collects the camera parameters, everything except the two points has a default:
+// +Y up, 90 degree vertical fov, the image aspect ratio and no depth of field
+#[derive(Copy, Clone, Debug)]
+pub struct CameraBuilder
+{
+    lookfrom:                   Vector3<f32>,
+    lookat:                     Vector3<f32>,
+    vup:                        Vector3<f32>,
+    vfov:                       f32,
+    aspect_ratio:               Option<f32>,
+    defocus_angle:              f32,
+    focus_dist:                 Option<f32>,
+    auto_focus:                 bool,
+    shutter_open:               f32,
+    shutter_close:              f32
+}
+
+impl CameraBuilder
+{
+    pub fn vup(mut self, vup: Vector3<f32>) -> Self
+    {
+        self.vup = vup;
+        self
+    }
+
+    // Vertical field of view in degrees
+    pub fn vfov(mut self, vfov: f32) -> Self
+    {
+        self.vfov = vfov;
+        self
+    }
+
+    // Width over height of the viewport, defaults to the aspect of the rendered image
+    pub fn aspect_ratio(mut self, aspect_ratio: f32) -> Self
+    {
+        self.aspect_ratio = Some(aspect_ratio);
+        self
+    }
+
+    // Apex angle in degrees of the cone of rays through each pixel, 0 is a pinhole
+    pub fn defocus_angle(mut self, defocus_angle: f32) -> Self
+    {
+        self.defocus_angle = defocus_angle;
+        self
+    }
+
+    // Distance from lookfrom to the plane in perfect focus, defaults to the distance to lookat
+    pub fn focus_dist(mut self, focus_dist: f32) -> Self
+    {
+        self.focus_dist = Some(focus_dist);
+        self.auto_focus = false;
+        self
+    }
+
+    // Rays are spread uniformly over [open, close], moving objects are at their
+    // start position at time 0 and at their end position at time 1
+    pub fn shutter(mut self, open: f32, close: f32) -> Self
+    {
+        self.shutter_open = open;
+        self.shutter_close = close;
+        self
+    }
+
+    // Focus on whatever is under the image centre, resolved once the scene is built
+    pub fn auto_focus(mut self) -> Self
+    {
+        self.auto_focus = true;
+        self
+    }
+
+    pub fn build(self) -> Camera
+    {
+        let focus_dist = self.focus_dist.unwrap_or_else(|| length(self.lookat - self.lookfrom));
+
+        let mut camera = Camera
+        {
+            _origin: self.lookfrom,
+            _direction: self.lookat,
+            _vup: self.vup,
+            _aspect_ratio: self.aspect_ratio,
+            _width: WINDOW_WIDTH,
+            _height: WINDOW_HEIGHT,
+            _fov: self.vfov,
+
+            _defocus_angle: self.defocus_angle,
+            _focus_dist: focus_dist,
+            _auto_focus: self.auto_focus,
+            _shutter_open: self.shutter_open,
+            _shutter_close: self.shutter_close,
+            _viewport_height: 0.0,
+            _u: Vector3::from_value(0.0),
+            _v: Vector3::from_value(0.0),
+            _w: Vector3::from_value(0.0),
+            _defocus_disk_u: Vector3::from_value(0.0),
+            _defocus_disk_v: Vector3::from_value(0.0),
+            _seed: 0,
+            _samples_per_pixel: SAMPLES_PER_PIXEL,
+            _max_depth: MAX_DEPTH,
+            _roulette_depth: ROULETTE_DEPTH,
+            _spectral: false
+        };
+        camera.update();
+        camera
+    }
+}
+
 #[derive(Copy, Clone, Debug)]
 pub struct Camera
 {
     // Public Parameters
     _origin:                    Vector3<f32>,
     _direction:                 Vector3<f32>,
-    _aspect_ratio:              f32,
+    _vup:                       Vector3<f32>,
+    _aspect_ratio:              Option<f32>,
     _width:                     u32,
     _height:                    u32,
     _fov:                       f32,
 
     // Private Parameters
     _defocus_angle:             f32,
-    _viewport_height:           f32,
     _focus_dist:                f32,
+    _auto_focus:                bool,
+    _shutter_open:              f32,
+    _shutter_close:             f32,
+    _viewport_height:           f32,
     _u:                         Vector3<f32>,
     _v:                         Vector3<f32>,
     _w:                         Vector3<f32>,
     _defocus_disk_u:            Vector3<f32>,
-    _defocus_disk_v:            Vector3<f32>
+    _defocus_disk_v:            Vector3<f32>,
+    _seed:                      u64,
+    _samples_per_pixel:         u32,
+    _max_depth:                 u32,
+    _roulette_depth:            u32,
+    _spectral:                  bool
 }
 
 impl Camera {
-    pub fn new(ori: Vector3<f32>, dir: Vector3<f32>, ar: f32, w: u32, h: u32, fov: f32) -> Camera
+    pub fn builder(lookfrom: Vector3<f32>, lookat: Vector3<f32>) -> CameraBuilder
     {
-        // defocus
-        //let focal_length = length(self.origin() - self.direction());
-        let defocus_angle = 10.0;
-        let focus_dist = 3.4;
+        CameraBuilder
+        {
+            lookfrom,
+            lookat,
+            vup: Vector3::new(0.0, 1.0, 0.0),
+            vfov: 90.0,
+            aspect_ratio: None,
+            defocus_angle: 0.0,
+            focus_dist: None,
+            auto_focus: false,
+            shutter_open: 0.0,
+            shutter_close: 0.0
+        }
+    }
 
+    // Recomputes the derived viewport and defocus parameters
+    fn update(&mut self)
+    {
         // calculation about fov
-        let theta = degrees_to_radians(fov.clone());
+        let theta = degrees_to_radians(self._fov);
         let in_h = (theta / 2.0).tan();
 
-        let viewport_height = 2.0 * in_h * focus_dist;
+        self._viewport_height = 2.0 * in_h * self._focus_dist;
 
         // Calculate the u,v,w unit basis vectors for the camera coordinate frame
-        let screen_w = unit_vector3(ori.clone() - dir.clone());
-        let screen_u = unit_vector3(Vector3::new(0.0, 1.0, 0.0).cross(screen_w.clone()));
-        let screen_v = screen_w.clone().cross(screen_u.clone());
+        self._w = unit_vector3(self._origin - self._direction);
+        self._u = unit_vector3(self._vup.cross(self._w));
+        self._v = self._w.cross(self._u);
 
         // Calculate the camera defocus disk basis vectors
-        let defocus_radius = focus_dist * (degrees_to_radians(defocus_angle / 2.0)).tan();
-        let defocus_disk_u = screen_u.clone() * defocus_radius;
-        let defocus_disk_v = screen_v.clone() * defocus_radius;
+        let defocus_radius = self._focus_dist * (degrees_to_radians(self._defocus_angle / 2.0)).tan();
+        self._defocus_disk_u = self._u * defocus_radius;
+        self._defocus_disk_v = self._v * defocus_radius;
+    }
 
-        Camera
-        {
-            _origin: ori,
-            _direction: dir,
-            _aspect_ratio: ar,
-            _width: w,
-            _height: h,
-            _fov: fov,
+    // Takes resolution, sample count, ray depths, seed and colour mode from the render settings
+    pub fn with_settings(mut self, settings: &RenderSettings) -> Camera
+    {
+        self._width = settings.width;
+        self._height = settings.height;
+        self._seed = settings.seed;
+        self._samples_per_pixel = settings.samples_per_pixel;
+        self._max_depth = settings.max_depth;
+        self._roulette_depth = settings.roulette_depth;
+        self._spectral = settings.spectral;
+        self
+    }
 
-            _defocus_angle: defocus_angle,
-            _viewport_height: viewport_height,
-            _focus_dist: focus_dist,
-            _u: screen_u,
-            _v: screen_v,
-            _w: screen_w,
-            _defocus_disk_u: defocus_disk_u,
-            _defocus_disk_v: defocus_disk_v
+    // Resolves auto-focus: the focus plane goes through the first surface hit by
+    // the ray through the image centre. Keeps the current distance on a miss.
+    pub fn focused_on(mut self, world: &dyn Hittable) -> Camera
+    {
+        if self._auto_focus
+        {
+            if let Some(hit) = world.hit(Ray::with_time(self._origin, -self._w, self._shutter_open), Interval::new(0.001, INFINITY))
+            {
+                self._focus_dist = hit.get_t();
+                self.update();
+            }
         }
+        self
+    }
+
+    // Global seed of the render, the same seed always produces the same image
+    pub fn seed(&self) -> u64
+    {
+        self._seed
+    }
+
+    pub fn samples_per_pixel(&self) -> u32
+    {
+        self._samples_per_pixel
+    }
+
+    pub fn max_depth(&self) -> u32
+    {
+        self._max_depth
+    }
+
+    pub fn roulette_depth(&self) -> u32
+    {
+        self._roulette_depth
+    }
+
+    // Whether paths carry wavelengths instead of RGB
+    pub fn spectral(&self) -> bool
+    {
+        self._spectral
+    }
+
+    pub fn focus_dist(&self) -> f32
+    {
+        self._focus_dist
+    }
+
+    pub fn defocus_angle(&self) -> f32
+    {
+        self._defocus_angle
+    }
+
+    pub fn vup(&self) -> Vector3<f32>
+    {
+        self._vup
+    }
+
+    pub fn shutter(&self) -> (f32, f32)
+    {
+        (self._shutter_open, self._shutter_close)
     }
 
     pub fn origin(&self) -> Vector3<f32>
@@ -84,9 +277,10 @@ impl Camera {
         self._direction
     }
 
+    // The explicit aspect ratio, otherwise that of the image
     pub fn aspect_ratio(&self) -> f32
     {
-        self._aspect_ratio
+        self._aspect_ratio.unwrap_or(self._width as f32 / self._height as f32)
     }
 
     pub fn width(&self) -> u32
@@ -104,10 +298,19 @@ impl Camera {
         self._fov
     }
 
-    pub fn render(&self, w: u32, h: u32, u: u32, v: u32, world: HittableList) -> Vector4<f32>
+    pub fn render(&self, w: u32, h: u32, u: u32, v: u32, scene: SceneView) -> Vector4<f32>
+    {
+        let color = self.sample_pixel(w, h, u, v, scene, 0..self._samples_per_pixel);
+        resolve_color(color, self._samples_per_pixel)
+    }
+
+    // Sum (not average) of the samples of pixel (u, v) numbered by `samples`, so
+    // that passes of a progressive render continue the same random sequence
+    pub fn sample_pixel(&self, w: u32, h: u32, u: u32, v: u32, scene: SceneView, samples: Range<u32>) -> Vector4<f32>
     {
         // Viewport
-        let viewport_width = self._viewport_height * (w as f32 / h as f32);
+        let aspect_ratio = self._aspect_ratio.unwrap_or(w as f32 / h as f32);
+        let viewport_width = self._viewport_height * aspect_ratio;
 
         // UV
         let viewport_u = viewport_width * self._u.clone();
@@ -119,19 +322,26 @@ impl Camera {
 
         let pixel00_loc = viewport_ul + 0.5 * (delta_u + delta_v);
 
-        let mut color = Vector4::new(0.0, 0.0, 0.0, 1.0);
-        for _ in 0..SAMPLES_PER_PIXEL
+        let mut color = Vector4::new(0.0, 0.0, 0.0, 0.0);
+        for s in samples
         {
+            seed_thread_sampler(self._seed, (u + v * w) as u64, s as u64);
             let r = Camera::get_ray(&self, pixel00_loc.clone(), u.clone(), delta_u.clone(), v.clone(), delta_v.clone());
 
-            color += Camera::ray_color(r, MAX_DEPTH, world.clone());
+            // Drawn after the ray so RGB renders keep their random sequence
+            let path = if self._spectral
+            {
+                PathColor::Spectral(SampledWavelengths::sample(random_double()))
+            }
+            else
+            {
+                PathColor::Rgb
+            };
+
+            color += self.ray_color(r.with_wavelength(path.wavelength()), path, scene.world, scene.lights, scene.background);
         }
 
-        let limit = Interval::new(0.0, 1.0);
-        Vector4::new(limit.clamp(linear_to_gamma(color.x / (SAMPLES_PER_PIXEL as f32))),
-                     limit.clamp(linear_to_gamma(color.y / (SAMPLES_PER_PIXEL as f32))),
-                     limit.clamp(linear_to_gamma(color.z / (SAMPLES_PER_PIXEL as f32))),
-                     limit.clamp(color.w / (SAMPLES_PER_PIXEL as f32)))
+        color
     }
 }
 
@@ -162,39 +372,178 @@ impl Camera
 
         let ray_dir = pixel_sample - ray_origin;
 
-        Ray::new(ray_origin, ray_dir)
+        // A closed shutter interval takes no random number, still images stay unchanged
+        let ray_time = if self._shutter_close > self._shutter_open
+        {
+            self._shutter_open + random_double() * (self._shutter_close - self._shutter_open)
+        }
+        else
+        {
+            self._shutter_open
+        };
+
+        Ray::with_time(ray_origin, ray_dir, ray_time)
     }
 
-    fn ray_color(r: Ray, depth: u32, world: HittableList) -> Vector4<f32>
+    // Follows one path from the camera for up to `_max_depth` bounces, keeping the
+    // product of the attenuations so far as its throughput. After `_roulette_depth`
+    // bounces a path survives each further bounce with probability equal to its
+    // brightest throughput channel and is scaled up by the inverse of that when it
+    // does, so dim paths end early without biasing the image. `path` says whether
+    // colour is carried as RGB or at sampled wavelengths, every RGB quantity met
+    // on the way is lifted into that form. The dielectric media the path is in
+    // are tracked to know the index beyond each surface and what absorbs light.
+    fn ray_color(&self, r: Ray, path: PathColor, world: &dyn Hittable, lights: Option<&dyn Hittable>, background: &dyn Background) -> Vector4<f32>
     {
-
-        if depth <= 0
+        let mut path = path;
+        let mut color = Vector4::from_value(0.0);
+        let mut throughput = Vector4::from_value(1.0);
+        let mut ray = r;
+        // Density with which a diffuse bounce picked `ray`, if it did. Emission
+        // found that way is weighted against the chance that light sampling at the
+        // same bounce found it too, so nothing is counted twice.
+        let mut bounce_pdf: Option<f32> = None;
+        let mut media = IorStack::new();
+        // Where along `ray` the current stretch starts, past any surfaces that
+        // were passed through. The origin stays put so light pdfs still hold.
+        let mut t_start = 0.0;
+
+        // Only counts surfaces the path interacted with, passing through one that
+        // isn't there doesn't use up depth
+        let mut bounce = 0;
+        while bounce < self._max_depth
         {
-            return Vector4::new(0.0, 0.0, 0.0, 1.0);
-        }
+            let hit = match world.hit(ray, Interval::new(t_start + 0.001, INFINITY))
+            {
+                Some(hit) => hit,
+                None =>
+                {
+                    color += throughput.mul_element_wise(path.lift(background.value(ray.direction())));
+                    break;
+                }
+            };
+
+            // Beer-Lambert absorption by the medium the stretch ran through
+            if let Some(medium) = media.current()
+            {
+                if medium.absorption != Vector3::from_value(0.0)
+                {
+                    let distance = (hit.get_t() - t_start) * length(ray.direction());
+                    throughput = throughput.mul_element_wise(path.lift((-medium.absorption * distance).map(f32::exp)));
+                }
+            }
+
+            // Inside a medium of higher priority the surface of a lower one isn't
+            // there, only note that the path crossed it and carry straight on
+            let material_id = Arc::as_ptr(&hit._material) as *const () as usize;
+            let interior = hit._material.interior(ray.wavelength());
+            if let Some(interior) = interior
+            {
+                // Entering, the far side is wherever the path is now. Leaving, it is
+                // what remains without this medium.
+                let surrounding = if hit._front_face { media.current() } else { media.surrounding(material_id) };
+                if surrounding.is_some_and(|s| s.priority > interior.priority)
+                {
+                    if hit._front_face { media.enter(material_id, interior) } else { media.leave(material_id) }
+                    t_start = hit.get_t();
+                    continue;
+                }
+                ray = ray.with_surrounding_ior(surrounding.map_or(1.0, |s| s.ior));
+            }
+            t_start = 0.0;
+
+            let mut emitted = hit._material.emitted(hit._u, hit._v, hit.get_point());
+            if let (Some(lights), Some(pdf)) = (lights, bounce_pdf)
+            {
+                emitted *= power_heuristic(pdf, lights.pdf_value(ray.origin(), ray.direction()));
+            }
+            color += throughput.mul_element_wise(path.lift(emitted));
 
-        if let Some(hit) = world.hit(r, Interval::new(0.001, INFINITY))
-        {
-            // let direction = rec.get_normal() + random_unit_vector3();
-            // return 0.1 * Camera::ray_color(Ray::new(rec.get_point(), direction), depth - 1, world);
             let mut scattered = Ray::new(Vector3::from_value(0.0), Vector3::from_value(0.0));
             let mut attenuation = Vector3::from_value(0.0);
-            if hit._material.scatter(&r, &hit, &mut attenuation, &mut scattered)
+            if !hit._material.scatter(&ray, &hit, &mut attenuation, &mut scattered)
+            {
+                break;
+            }
+
+            // Specular bounces can't be combined with light sampling, follow them as they are
+            let scattering_pdf = hit._material.scattering_pdf(&ray, &hit, &scattered);
+            if scattering_pdf > 0.0
             {
-                let ray_color = Camera::ray_color(scattered, depth - 1, world);
-                return Vector4::new(ray_color.x * attenuation.x,
-                                    ray_color.y * attenuation.y,
-                                    ray_color.z * attenuation.z,
-                                        ray_color.w);
+                if let Some(lights) = lights
+                {
+                    color += throughput.mul_element_wise(Camera::sample_lights(&ray, &path, &hit, world, lights));
+                }
+                bounce_pdf = Some(scattering_pdf);
             }
             else
             {
-                return Vector4::new(0.0, 0.0, 0.0, 1.0);
+                bounce_pdf = None;
+            }
+
+            if hit._material.is_dispersive()
+            {
+                path.terminate_secondary(&mut throughput);
+            }
+
+            // Transmission through a medium's surface moves the path in or out of it
+            if let Some(interior) = interior
+            {
+                if dot(scattered.direction(), hit.get_normal()) < 0.0
+                {
+                    if hit._front_face { media.enter(material_id, interior) } else { media.leave(material_id) }
+                }
+            }
+
+            // `scatter` returned eval / pdf of the direction it picked as the attenuation
+            throughput = throughput.mul_element_wise(path.lift(attenuation));
+            ray = scattered.with_wavelength(path.wavelength());
+
+            bounce += 1;
+            if bounce >= self._roulette_depth
+            {
+                let survival = throughput.x.max(throughput.y).max(throughput.z).max(throughput.w).min(1.0);
+                if random_double() >= survival
+                {
+                    break;
+                }
+                throughput /= survival;
+            }
+        }
+
+        path.resolve(color).extend(1.0)
+    }
+
+    // Next event estimation: radiance arriving at a diffuse hit along one shadow ray
+    // aimed at the lights, weighted against the material's own sampling
+    fn sample_lights(r: &Ray, path: &PathColor, hit: &HitRecord, world: &dyn Hittable, lights: &dyn Hittable) -> Vector4<f32>
+    {
+        let light_pdf = HittablePdf::new(lights, hit.get_point());
+        let direction = light_pdf.generate();
+        let pdf = light_pdf.value(direction);
+        if pdf <= 0.0
+        {
+            return Vector4::from_value(0.0);
+        }
+
+        let shadow_ray = Ray::with_time(hit.get_point(), direction, r.time()).with_wavelength(r.wavelength());
+        let scattering_pdf = hit._material.scattering_pdf(r, hit, &shadow_ray);
+        if scattering_pdf <= 0.0
+        {
+            return Vector4::from_value(0.0);
+        }
+
+        // Whatever the shadow ray hits first is what lights the point, occluders emit nothing
+        match world.hit(shadow_ray, Interval::new(0.001, INFINITY))
+        {
+            Some(light) =>
+            {
+                let emitted = light._material.emitted(light._u, light._v, light.get_point());
+                let eval = hit._material.eval(r, hit, &shadow_ray);
+                path.lift(emitted).mul_element_wise(path.lift(eval)) * (power_heuristic(pdf, scattering_pdf) / pdf)
             }
+            None => Vector4::from_value(0.0)
         }
-        let unit_dir = unit_vector3(r.direction());
-        let a = 0.5 * (unit_dir.y + 1.0);
-        (1.0 - a) * Vector4::from_value(1.0) + a * Vector4::new(0.5, 0.7, 1.0, 1.0)
     }
 
     fn defocus_disk_sample(&self, defocus_disk_u: Vector3<f32>, defocus_disk_v: Vector3<f32>) -> Vector3<f32>
@@ -213,11 +562,220 @@ mod test
     #[test]
     fn test()
     {
-        let cam = Camera::new(Vector3::from_value(0.0), Vector3::from_value(1.0), 16.0 / 9.0, 1600, 900, 1.0);
+        let cam = Camera::builder(Vector3::from_value(0.0), Vector3::from_value(1.0)).aspect_ratio(16.0 / 9.0).vfov(1.0).build();
+        assert_eq!(cam.aspect_ratio(), 16.0 / 9.0);
+        assert_eq!(cam.fov(), 1.0);
+        assert_eq!((cam.width(), cam.height()), (WINDOW_WIDTH, WINDOW_HEIGHT));
+
+        // w points back from lookat, u to the right of it and v up, all orthonormal
+        assert!((cam._w + Vector3::from_value(1.0 / 3.0f32.sqrt())).magnitude() < 1e-6);
+        assert!((cam._u - Vector3::new(-1.0, 0.0, 1.0) / 2.0f32.sqrt()).magnitude() < 1e-6);
+        assert!((cam._v.magnitude() - 1.0).abs() < 1e-6);
+        assert!(cam._v.dot(cam._u).abs() < 1e-6 && cam._v.dot(cam._w).abs() < 1e-6 && cam._v.y > 0.0);
+
+        // Without an explicit aspect ratio the image's is used
+        let settings = RenderSettings { width: 300, height: 200, ..RenderSettings::default() };
+        let cam = Camera::builder(Vector3::from_value(0.0), Vector3::from_value(1.0)).build().with_settings(&settings);
+        assert_eq!((cam.width(), cam.height()), (300, 200));
+        assert_eq!(cam.aspect_ratio(), 1.5);
+    }
+
+    #[test]
+    fn test_vup_rolls_camera()
+    {
+        let cam = Camera::builder(Vector3::new(0.0, 0.0, 0.0), Vector3::new(0.0, 0.0, -1.0)).vup(Vector3::new(1.0, 0.0, 0.0)).build();
+        assert!((cam._v - Vector3::new(1.0, 0.0, 0.0)).magnitude() < 1e-6);
+        assert!((cam._u - Vector3::new(0.0, -1.0, 0.0)).magnitude() < 1e-6);
+    }
+
+    #[test]
+    fn test_auto_focus()
+    {
+        use std::sync::Arc;
+        use crate::renderer::hittable_list::HittableList;
+        use crate::renderer::material::Lambertian;
+        use crate::renderer::sphere::Sphere;
+
+        let mut world = HittableList::new();
+        world.add(Arc::new(Sphere::new(Vector3::new(0.0, 0.0, -5.0), 1.0, Arc::new(Lambertian::new(Vector3::from_value(0.5))))));
+
+        let builder = Camera::builder(Vector3::new(0.0, 0.0, 0.0), Vector3::new(0.0, 0.0, -1.0)).defocus_angle(2.0);
+        assert_eq!(builder.build().focus_dist(), 1.0);
+        assert!((builder.auto_focus().build().focused_on(&world).focus_dist() - 4.0).abs() < 1e-4);
+        assert_eq!(builder.focus_dist(2.5).build().focused_on(&world).focus_dist(), 2.5);
+    }
+
+    #[test]
+    fn test_seeded_render_is_reproducible()
+    {
+        use std::sync::Arc;
+        use std::thread;
+        use crate::renderer::render_backend::build_scene;
+
+        let scene = Arc::new(build_scene(32, 24));
+        let forward: Vec<Vector4<f32>> = (0..32).map(|u| scene.render_pixel(32, 24, u, 12)).collect();
+
+        // Render order and thread must not matter
+        let local_scene = Arc::clone(&scene);
+        let mut backward: Vec<Vector4<f32>> = thread::spawn(move || {
+            (0..32).rev().map(|u| local_scene.render_pixel(32, 24, u, 12)).collect()
+        }).join().unwrap();
+        backward.reverse();
+
+        assert_eq!(forward, backward);
+    }
+
+    // Glows and reflects at once, only used to light the furnace below
+    struct GlowingLambertian
+    {
+        diffuse: crate::renderer::material::Lambertian,
+        emit: f32
+    }
+
+    impl Material for GlowingLambertian
+    {
+        fn scatter(&self, r_in: &Ray, rec: &HitRecord, attenuation: &mut Vector3<f32>, scattered: &mut Ray) -> bool
+        {
+            self.diffuse.scatter(r_in, rec, attenuation, scattered)
+        }
+
+        fn emitted(&self, _u: f32, _v: f32, _p: Vector3<f32>) -> Vector3<f32>
+        {
+            Vector3::from_value(self.emit)
+        }
+
+        fn scattering_pdf(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f32
+        {
+            self.diffuse.scattering_pdf(r_in, rec, scattered)
+        }
+
+        fn eval(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Vector3<f32>
+        {
+            self.diffuse.eval(r_in, rec, scattered)
+        }
+    }
+
+    #[test]
+    fn test_russian_roulette_is_unbiased()
+    {
+        use std::sync::Arc;
+        use crate::renderer::hittable_list::HittableList;
+        use crate::renderer::material::Lambertian;
+        use crate::renderer::scene::Scene;
+        use crate::renderer::sphere::Sphere;
+
+        // Inside a closed sphere that emits 0.25 and reflects half of what it
+        // receives, every path sees 0.25 (1 + 0.5 + 0.25 + ...) = 0.5
+        let material = Arc::new(GlowingLambertian { diffuse: Lambertian::new(Vector3::from_value(0.5)), emit: 0.25 });
+        let mut world = HittableList::new();
+        world.add(Arc::new(Sphere::new(Vector3::from_value(0.0), 1.0, material)));
+        let camera = Camera::builder(Vector3::from_value(0.0), Vector3::new(0.0, 0.0, -1.0)).build();
+
+        let samples = 20_000;
+        for &roulette_depth in &[0, 200]
+        {
+            let settings = RenderSettings { width: 1, height: 1, max_depth: 200, roulette_depth: roulette_depth, ..RenderSettings::default() };
+            let scene = Scene::new(camera.with_settings(&settings), world.clone());
+            let mean = scene.sample_pixel(1, 1, 0, 0, 0, samples) / samples as f32;
+            assert!((mean.x - 0.5).abs() < 0.01, "roulette depth {}: {}", roulette_depth, mean.x);
+        }
+    }
+
+    #[test]
+    fn test_absorption_follows_the_medium_stack()
+    {
+        use std::sync::Arc;
+        use crate::renderer::background::SolidBackground;
+        use crate::renderer::hittable_list::HittableList;
+        use crate::renderer::material::Dielectric;
+        use crate::renderer::quad::Cuboid;
+        use crate::renderer::scene::Scene;
+
+        // Index matched slabs, so nothing refracts: glass over z in [-3, -1] and a
+        // lower priority liquid over [-5, -2]. Where they overlap only the glass
+        // absorbs, so a ray down -z keeps exp(-(0.5 * 2 + 1 * 2)).
+        let glass = Arc::new(Dielectric::new(1.0).absorbing(Vector3::new(0.5, 0.0, 0.0)).with_priority(1));
+        let liquid = Arc::new(Dielectric::new(1.0).absorbing(Vector3::new(1.0, 0.0, 0.0)));
+        let mut world = HittableList::new();
+        world.add(Arc::new(Cuboid::new(Vector3::new(-10.0, -10.0, -3.0), Vector3::new(10.0, 10.0, -1.0), glass)));
+        world.add(Arc::new(Cuboid::new(Vector3::new(-10.0, -10.0, -5.0), Vector3::new(10.0, 10.0, -2.0), liquid)));
+
+        let camera = Camera::builder(Vector3::from_value(0.0), Vector3::new(0.0, 0.0, -1.0)).vfov(0.1).build();
+        let settings = RenderSettings { width: 1, height: 1, ..RenderSettings::default() };
+        let scene = Scene::new(camera.with_settings(&settings), world).with_background(Arc::new(SolidBackground::new(Vector3::from_value(1.0))));
+
+        let color = scene.sample_pixel(1, 1, 0, 0, 0, 16) / 16.0;
+        assert!((color.x - (-3.0f32).exp()).abs() < 1e-4, "{}", color.x);
+        assert_eq!(color.y, 1.0);
+    }
+
+    // Fraction of the light behind `world` that reaches the camera straight through it
+    fn transmitted(world: crate::renderer::hittable_list::HittableList, max_depth: u32, samples: u32) -> f32
+    {
+        use std::sync::Arc;
+        use crate::renderer::background::SolidBackground;
+        use crate::renderer::material::DiffuseLight;
+        use crate::renderer::quad::Quad;
+        use crate::renderer::scene::Scene;
+
+        let mut world = world;
+        let light = Arc::new(DiffuseLight::new(Vector3::from_value(1.0)));
+        world.add(Arc::new(Quad::new(Vector3::new(-50.0, -50.0, -10.0), Vector3::new(100.0, 0.0, 0.0), Vector3::new(0.0, 100.0, 0.0), light)));
+
+        let camera = Camera::builder(Vector3::from_value(0.0), Vector3::new(0.0, 0.0, -1.0)).vfov(0.1).build();
+        let settings = RenderSettings { width: 1, height: 1, max_depth: max_depth, ..RenderSettings::default() };
+        let scene = Scene::new(camera.with_settings(&settings), world).with_background(Arc::new(SolidBackground::new(Vector3::from_value(0.0))));
+        scene.sample_pixel(1, 1, 0, 0, 0, samples).x / samples as f32
+    }
+
+    #[test]
+    fn test_overlapping_objects_sharing_a_material()
+    {
+        use std::sync::Arc;
+        use crate::renderer::hittable_list::HittableList;
+        use crate::renderer::material::{Dielectric, RoughDielectric};
+        use crate::renderer::principled::Principled;
+        use crate::renderer::quad::Cuboid;
+
+        // Two overlapping glass slabs are one piece of glass: only the outer two
+        // surfaces reflect 4% each, the ones inside the overlap are index matched.
+        // Principled picks its lobes at random, so it needs more samples.
+        let materials: [(Arc<dyn Material>, u32); 3] = [
+            (Arc::new(Dielectric::new(1.5)), 20_000),
+            (Arc::new(RoughDielectric::new(1.5, 0.0, 0.0)), 20_000),
+            (Arc::new(Principled::new(Vector3::from_value(1.0)).roughness(0.0).transmission(1.0)), 100_000)];
+        for (glass, samples) in materials.iter()
+        {
+            let mut world = HittableList::new();
+            world.add(Arc::new(Cuboid::new(Vector3::new(-10.0, -10.0, -3.0), Vector3::new(10.0, 10.0, -1.0), glass.clone())));
+            world.add(Arc::new(Cuboid::new(Vector3::new(-10.0, -10.0, -4.0), Vector3::new(10.0, 10.0, -2.0), glass.clone())));
+
+            let fraction = transmitted(world, RenderSettings::default().max_depth, *samples);
+            assert!((fraction - 0.96 * 0.96).abs() < 0.015, "{}", fraction);
+        }
+    }
+
+    #[test]
+    fn test_skipped_surfaces_keep_their_depth()
+    {
+        use std::sync::Arc;
+        use crate::renderer::hittable_list::HittableList;
+        use crate::renderer::material::Dielectric;
+        use crate::renderer::quad::Cuboid;
+
+        // Four lower priority slabs inside an index matched one: the light is
+        // three bounces away however many surfaces the path passes on the way
+        let outer = Arc::new(Dielectric::new(1.0).with_priority(1));
+        let inner = Arc::new(Dielectric::new(1.5));
+        let mut world = HittableList::new();
+        world.add(Arc::new(Cuboid::new(Vector3::new(-10.0, -10.0, -9.0), Vector3::new(10.0, 10.0, -1.0), outer)));
+        for i in 0..4
+        {
+            let z = -2.0 - 1.5 * i as f32;
+            world.add(Arc::new(Cuboid::new(Vector3::new(-10.0, -10.0, z - 1.0), Vector3::new(10.0, 10.0, z), inner.clone())));
+        }
 
-        println!(
-            "{:?}, {:?}, {}, {}, {}, {}",
-            cam.origin(), cam.direction(), cam.aspect_ratio(), cam.width(), cam.height(), cam.fov()
-        )
+        let fraction = transmitted(world, 3, 16);
+        assert!((fraction - 1.0).abs() < 1e-4, "{}", fraction);
     }
 }
\ No newline at end of file
@@ -1,7 +1,7 @@
 use cgmath::{dot, Vector3};
 use num::abs;
-use rand::Rng;
 use crate::renderer::ray::Ray;
+use crate::renderer::sampler;
 use crate::utility::constants::PI;
 
 
@@ -23,11 +23,11 @@ pub fn unit_vector3(v: Vector3<f32>) -> Vector3<f32>
     v / length(v)
 }
 
+// Uniform in [0, 1), drawn from the calling thread's seeded sampler
 #[inline]
 pub fn random_double() -> f32
 {
-    let mut rng = rand::thread_rng();
-    rng.gen_range(0.0..1.0)
+    sampler::next_f32()
 }
 
 #[inline]
@@ -36,6 +36,13 @@ pub fn random_double_range(min: f32, max: f32) -> f32
     min + (max - min) * random_double()
 }
 
+// Integer in [min, max]
+#[inline]
+pub fn random_int(min: usize, max: usize) -> usize
+{
+    (min + (random_double() * (max - min + 1) as f32) as usize).min(max)
+}
+
 #[inline]
 pub fn random_vector3() -> Vector3<f32>
 {
@@ -95,12 +102,41 @@ pub fn random_on_hemisphere(normal: Vector3<f32>) -> Vector3<f32>
         -on_unit_sphere
     }
 }
+
+// Direction around +Z with density cos(theta) / pi
+#[inline]
+pub fn random_cosine_direction() -> Vector3<f32>
+{
+    let r1 = random_double();
+    let r2 = random_double();
+
+    let phi = 2.0 * PI * r1;
+    let x = phi.cos() * r2.sqrt();
+    let y = phi.sin() * r2.sqrt();
+    let z = (1.0 - r2).sqrt();
+
+    Vector3::new(x, y, z)
+}
 #[inline]
 pub fn linear_to_gamma(linear_component: f32) -> f32
 {
     linear_component.sqrt()
 }
 
+// Decode an 8-bit sRGB channel (images on disk) to linear [0, 1]
+pub fn srgb_to_linear(c: u8) -> f32
+{
+    let c = c as f32 / 255.0;
+    if c <= 0.04045
+    {
+        c / 12.92
+    }
+    else
+    {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
 pub fn near_zero(v: Vector3<f32>) -> bool
 {
     let s = 1e-8;
@@ -128,6 +164,17 @@ pub fn degrees_to_radians(degrees: f32) -> f32
     degrees * PI / 180.0
 }
 
+// Two unit vectors that together with the unit vector `n` form a right-handed
+// orthonormal basis (Duff et al. 2017, "Building an Orthonormal Basis, Revisited")
+pub fn orthonormal_basis(n: Vector3<f32>) -> (Vector3<f32>, Vector3<f32>)
+{
+    let sign = 1.0f32.copysign(n.z);
+    let a = -1.0 / (sign + n.z);
+    let b = n.x * n.y * a;
+    (Vector3::new(1.0 + sign * n.x * n.x * a, sign * b, -sign * n.x),
+     Vector3::new(b, sign + n.y * n.y * a, -n.y))
+}
+
 pub fn set_face_normal(r: Ray, outward_normal: Vector3<f32>) -> (Vector3<f32>, bool)
 {
     // Sets the hit record normal vector
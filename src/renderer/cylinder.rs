@@ -0,0 +1,396 @@
+use std::sync::Arc;
+use cgmath::{dot, Vector3};
+use crate::renderer::aabb::Aabb;
+use crate::renderer::custom_function::{length, orthonormal_basis, random_double, random_in_unit_disk, set_face_normal, unit_vector3};
+use crate::renderer::hittable::{area_pdf_value, HitRecord, Hittable};
+use crate::renderer::interval::Interval;
+use crate::renderer::material::Material;
+use crate::renderer::ray::Ray;
+use crate::utility::constants::PI;
+
+// Frame with its origin at the base centre and local z along the axis, so both
+// quadrics below can be intersected in their canonical position
+#[derive(Copy, Clone, Debug)]
+struct AxisFrame
+{
+    _origin: Vector3<f32>,
+    _x: Vector3<f32>,
+    _y: Vector3<f32>,
+    _z: Vector3<f32>,
+    _height: f32
+}
+
+impl AxisFrame
+{
+    fn new(base: Vector3<f32>, top: Vector3<f32>) -> Self
+    {
+        let axis = top - base;
+        let z = unit_vector3(axis);
+        let (x, y) = orthonormal_basis(z);
+        AxisFrame { _origin: base, _x: x, _y: y, _z: z, _height: length(axis) }
+    }
+
+    fn to_local(self, v: Vector3<f32>) -> Vector3<f32>
+    {
+        Vector3::new(dot(v, self._x), dot(v, self._y), dot(v, self._z))
+    }
+
+    fn to_world(self, v: Vector3<f32>) -> Vector3<f32>
+    {
+        v.x * self._x + v.y * self._y + v.z * self._z
+    }
+
+    fn point_to_world(&self, p: Vector3<f32>) -> Vector3<f32>
+    {
+        self._origin + self.to_world(p)
+    }
+
+    // Uniform point on the disk of `radius` at height `z`, in local coordinates
+    fn random_on_cap(z: f32, radius: f32) -> Vector3<f32>
+    {
+        let p = radius * random_in_unit_disk();
+        Vector3::new(p.x, p.y, z)
+    }
+
+    // Box around the two end circles, `radius_base` at z = 0 and `radius_top` at the top
+    fn bounding_box(&self, radius_base: f32, radius_top: f32) -> Aabb
+    {
+        let top = self._origin + self._height * self._z;
+        let disk_extent = |r: f32| Vector3::new((1.0 - self._z.x * self._z.x).max(0.0).sqrt(),
+                                                (1.0 - self._z.y * self._z.y).max(0.0).sqrt(),
+                                                (1.0 - self._z.z * self._z.z).max(0.0).sqrt()) * r;
+        let base_box = Aabb::from_points(self._origin - disk_extent(radius_base), self._origin + disk_extent(radius_base));
+        let top_box = Aabb::from_points(top - disk_extent(radius_top), top + disk_extent(radius_top));
+        Aabb::surrounding(&base_box, &top_box)
+    }
+
+    // Hit record from a local-space point and outward normal
+    fn record(&self, ray: &Ray, t: f32, local_point: Vector3<f32>, local_normal: Vector3<f32>, v: f32, material: &Arc<dyn Material>) -> HitRecord
+    {
+        let outward_normal = unit_vector3(self.to_world(local_normal));
+        let (normal, front_face) = set_face_normal(*ray, outward_normal);
+        let u = local_point.y.atan2(local_point.x) / (2.0 * PI) + 0.5;
+        HitRecord{ _t: t, _point: ray.at(t), _normal: normal, _material: material.clone(), _u: u, _v: v, _front_face: front_face }
+    }
+}
+
+// Roots of a t^2 + b t + c in ascending order, a linear equation when a is zero
+fn solve_quadratic(a: f32, b: f32, c: f32) -> Option<(f32, f32)>
+{
+    if a.abs() < 1e-12
+    {
+        if b.abs() < 1e-12
+        {
+            return None;
+        }
+        let t = -c / b;
+        return Some((t, t));
+    }
+
+    let discriminant = b * b - 4.0 * a * c;
+    if discriminant < 0.0
+    {
+        return None;
+    }
+    let sqrtd = discriminant.sqrt();
+    let (t0, t1) = ((-b - sqrtd) / (2.0 * a), (-b + sqrtd) / (2.0 * a));
+    if t0 <= t1 { Some((t0, t1)) } else { Some((t1, t0)) }
+}
+
+// Distance to the cap plane z = `height` if the hit lies within `radius`
+fn cap_hit(origin: Vector3<f32>, direction: Vector3<f32>, height: f32, radius: f32) -> Option<f32>
+{
+    if direction.z.abs() < 1e-12
+    {
+        return None;
+    }
+    let t = (height - origin.z) / direction.z;
+    let p = origin + t * direction;
+    if p.x * p.x + p.y * p.y <= radius * radius { Some(t) } else { None }
+}
+
+// Closed cylinder between the centres of its two caps. u runs around the
+// axis; v runs from base to top along the side and from the centre outwards on the caps.
+pub struct Cylinder
+{
+    _frame: AxisFrame,
+    _radius: f32,
+    _material: Arc<dyn Material>,
+    _bbox: Aabb
+}
+
+impl Cylinder
+{
+    pub fn new(base: Vector3<f32>, top: Vector3<f32>, radius: f32, material: Arc<dyn Material>) -> Self
+    {
+        let frame = AxisFrame::new(base, top);
+        Cylinder
+        {
+            _frame: frame,
+            _radius: radius,
+            _material: material,
+            _bbox: frame.bounding_box(radius, radius)
+        }
+    }
+
+    fn side_area(&self) -> f32
+    {
+        2.0 * PI * self._radius * self._frame._height
+    }
+
+    fn cap_area(&self) -> f32
+    {
+        PI * self._radius * self._radius
+    }
+}
+
+impl Hittable for Cylinder
+{
+    fn hit(&self, ray: Ray, ray_t: Interval) -> Option<HitRecord>
+    {
+        let frame = &self._frame;
+        let o = frame.to_local(ray.origin() - frame._origin);
+        let d = frame.to_local(ray.direction());
+        let h = frame._height;
+
+        let mut closest = ray_t;
+        let mut result = None;
+
+        // Side: x^2 + y^2 = r^2 with 0 <= z <= h
+        let a = d.x * d.x + d.y * d.y;
+        let b = 2.0 * (o.x * d.x + o.y * d.y);
+        let c = o.x * o.x + o.y * o.y - self._radius * self._radius;
+        if a > 1e-12
+        {
+            if let Some((t0, t1)) = solve_quadratic(a, b, c)
+            {
+                for &t in &[t0, t1]
+                {
+                    let p = o + t * d;
+                    if closest.surrounds(t) && p.z >= 0.0 && p.z <= h
+                    {
+                        result = Some(frame.record(&ray, t, p, Vector3::new(p.x, p.y, 0.0), p.z / h, &self._material));
+                        closest = Interval::new(closest.min(), t);
+                        break;
+                    }
+                }
+            }
+        }
+
+        // Caps
+        for &(z, nz) in &[(0.0, -1.0), (h, 1.0)]
+        {
+            if let Some(t) = cap_hit(o, d, z, self._radius)
+            {
+                if closest.surrounds(t)
+                {
+                    let p = o + t * d;
+                    let v = (p.x * p.x + p.y * p.y).sqrt() / self._radius;
+                    result = Some(frame.record(&ray, t, p, Vector3::new(0.0, 0.0, nz), v, &self._material));
+                    closest = Interval::new(closest.min(), t);
+                }
+            }
+        }
+
+        result
+    }
+
+    fn bounding_box(&self) -> Aabb
+    {
+        self._bbox
+    }
+
+    // Uniform over the side and both caps
+    fn pdf_value(&self, origin: Vector3<f32>, direction: Vector3<f32>) -> f32
+    {
+        area_pdf_value(self, self.side_area() + 2.0 * self.cap_area(), origin, direction)
+    }
+
+    fn random(&self, origin: Vector3<f32>) -> Vector3<f32>
+    {
+        let (side, cap, h) = (self.side_area(), self.cap_area(), self._frame._height);
+        let pick = random_double() * (side + 2.0 * cap);
+        let local = if pick < side
+        {
+            let phi = 2.0 * PI * random_double();
+            Vector3::new(self._radius * phi.cos(), self._radius * phi.sin(), h * random_double())
+        }
+        else
+        {
+            AxisFrame::random_on_cap(if pick < side + cap { 0.0 } else { h }, self._radius)
+        };
+        self._frame.point_to_world(local) - origin
+    }
+}
+
+// Closed cone with a disk of `radius` at `base` narrowing to a point at `apex`.
+// Texture coordinates follow the cylinder.
+pub struct Cone
+{
+    _frame: AxisFrame,
+    _radius: f32,
+    _material: Arc<dyn Material>,
+    _bbox: Aabb
+}
+
+impl Cone
+{
+    pub fn new(base: Vector3<f32>, apex: Vector3<f32>, radius: f32, material: Arc<dyn Material>) -> Self
+    {
+        let frame = AxisFrame::new(base, apex);
+        Cone
+        {
+            _frame: frame,
+            _radius: radius,
+            _material: material,
+            _bbox: frame.bounding_box(radius, 0.0)
+        }
+    }
+
+    fn side_area(&self) -> f32
+    {
+        let slant = (self._radius * self._radius + self._frame._height * self._frame._height).sqrt();
+        PI * self._radius * slant
+    }
+
+    fn base_area(&self) -> f32
+    {
+        PI * self._radius * self._radius
+    }
+}
+
+impl Hittable for Cone
+{
+    fn hit(&self, ray: Ray, ray_t: Interval) -> Option<HitRecord>
+    {
+        let frame = &self._frame;
+        let o = frame.to_local(ray.origin() - frame._origin);
+        let d = frame.to_local(ray.direction());
+        let h = frame._height;
+
+        let mut closest = ray_t;
+        let mut result = None;
+
+        // Side: x^2 + y^2 = (k (h - z))^2 with k = r / h and 0 <= z <= h
+        let k2 = (self._radius / h) * (self._radius / h);
+        let hz = h - o.z;
+        let a = d.x * d.x + d.y * d.y - k2 * d.z * d.z;
+        let b = 2.0 * (o.x * d.x + o.y * d.y + k2 * hz * d.z);
+        let c = o.x * o.x + o.y * o.y - k2 * hz * hz;
+        if let Some((t0, t1)) = solve_quadratic(a, b, c)
+        {
+            for &t in &[t0, t1]
+            {
+                let p = o + t * d;
+                if closest.surrounds(t) && p.z >= 0.0 && p.z <= h
+                {
+                    let normal = Vector3::new(p.x, p.y, k2 * (h - p.z));
+                    result = Some(frame.record(&ray, t, p, normal, p.z / h, &self._material));
+                    closest = Interval::new(closest.min(), t);
+                    break;
+                }
+            }
+        }
+
+        // Base cap
+        if let Some(t) = cap_hit(o, d, 0.0, self._radius)
+        {
+            if closest.surrounds(t)
+            {
+                let p = o + t * d;
+                let v = (p.x * p.x + p.y * p.y).sqrt() / self._radius;
+                result = Some(frame.record(&ray, t, p, Vector3::new(0.0, 0.0, -1.0), v, &self._material));
+            }
+        }
+
+        result
+    }
+
+    fn bounding_box(&self) -> Aabb
+    {
+        self._bbox
+    }
+
+    // Uniform over the side and the base
+    fn pdf_value(&self, origin: Vector3<f32>, direction: Vector3<f32>) -> f32
+    {
+        area_pdf_value(self, self.side_area() + self.base_area(), origin, direction)
+    }
+
+    fn random(&self, origin: Vector3<f32>) -> Vector3<f32>
+    {
+        let side = self.side_area();
+        let local = if random_double() * (side + self.base_area()) < side
+        {
+            // The circle at distance s from the apex grows linearly with s
+            let s = random_double().sqrt();
+            let phi = 2.0 * PI * random_double();
+            Vector3::new(self._radius * s * phi.cos(), self._radius * s * phi.sin(), self._frame._height * (1.0 - s))
+        }
+        else
+        {
+            AxisFrame::random_on_cap(0.0, self._radius)
+        };
+        self._frame.point_to_world(local) - origin
+    }
+}
+
+#[cfg(test)]
+mod test
+{
+    use cgmath::{Array, InnerSpace};
+    use super::*;
+    use crate::renderer::material::Lambertian;
+
+    fn material() -> Arc<dyn Material>
+    {
+        Arc::new(Lambertian::new(Vector3::from_value(0.5)))
+    }
+
+    fn hit(object: &dyn Hittable, origin: Vector3<f32>, direction: Vector3<f32>) -> Option<HitRecord>
+    {
+        object.hit(Ray::new(origin, direction), Interval::new(0.001, f32::INFINITY))
+    }
+
+    #[test]
+    fn test_cylinder_side_and_caps()
+    {
+        // Upright cylinder of radius 1 from y = 0 to y = 2
+        let cylinder = Cylinder::new(Vector3::new(0.0, 0.0, 0.0), Vector3::new(0.0, 2.0, 0.0), 1.0, material());
+
+        let side = hit(&cylinder, Vector3::new(5.0, 1.0, 0.0), Vector3::new(-1.0, 0.0, 0.0)).unwrap();
+        assert!((side.get_t() - 4.0).abs() < 1e-5);
+        assert!((side.get_normal() - Vector3::new(1.0, 0.0, 0.0)).magnitude() < 1e-5);
+        assert!((side._v - 0.5).abs() < 1e-5);
+
+        let top = hit(&cylinder, Vector3::new(0.5, 5.0, 0.0), Vector3::new(0.0, -1.0, 0.0)).unwrap();
+        assert!((top.get_t() - 3.0).abs() < 1e-5);
+        assert!((top.get_normal() - Vector3::new(0.0, 1.0, 0.0)).magnitude() < 1e-5);
+
+        // From inside, the bottom cap is seen from its back
+        let bottom = hit(&cylinder, Vector3::new(0.0, 1.0, 0.0), Vector3::new(0.0, -1.0, 0.0)).unwrap();
+        assert!(!bottom._front_face);
+
+        assert!(hit(&cylinder, Vector3::new(5.0, 3.0, 0.0), Vector3::new(-1.0, 0.0, 0.0)).is_none());
+        assert!((cylinder.bounding_box().max() - Vector3::new(1.0, 2.0, 1.0)).magnitude() < 1e-3);
+    }
+
+    #[test]
+    fn test_cone_side_and_base()
+    {
+        // Radius 1 at y = 0 narrowing to the apex at y = 1
+        let cone = Cone::new(Vector3::new(0.0, 0.0, 0.0), Vector3::new(0.0, 1.0, 0.0), 1.0, material());
+
+        // Halfway up the radius is 0.5 and the slope normal points out and up at 45 degrees
+        let side = hit(&cone, Vector3::new(5.0, 0.5, 0.0), Vector3::new(-1.0, 0.0, 0.0)).unwrap();
+        assert!((side.get_t() - 4.5).abs() < 1e-5);
+        let expected = Vector3::new(1.0, 1.0, 0.0).normalize();
+        assert!((side.get_normal() - expected).magnitude() < 1e-5);
+
+        let base = hit(&cone, Vector3::new(0.2, -3.0, 0.0), Vector3::new(0.0, 1.0, 0.0)).unwrap();
+        assert!((base.get_t() - 3.0).abs() < 1e-5);
+        assert!(base._front_face);
+
+        assert!(hit(&cone, Vector3::new(5.0, 0.9, 0.5), Vector3::new(-1.0, 0.0, 0.0)).is_none());
+    }
+}
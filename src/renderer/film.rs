@@ -0,0 +1,448 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Read, Write};
+use std::path::Path;
+use cgmath::Vector4;
+use crate::renderer::custom_function::linear_to_gamma;
+use crate::renderer::interval::Interval;
+
+// Average `samples` accumulated radiance samples into a display-ready colour
+pub fn resolve_color(sum: Vector4<f32>, samples: u32) -> Vector4<f32>
+{
+    if samples == 0
+    {
+        return Vector4::new(0.0, 0.0, 0.0, 1.0);
+    }
+
+    let n = samples as f32;
+    let limit = Interval::new(0.0, 1.0);
+    Vector4::new(limit.clamp(linear_to_gamma(sum.x / n)),
+                 limit.clamp(linear_to_gamma(sum.y / n)),
+                 limit.clamp(linear_to_gamma(sum.z / n)),
+                 limit.clamp(sum.w / n))
+}
+
+// Accumulation buffer: running sum of samples and the sample count of every pixel
+pub struct Film
+{
+    _width: u32,
+    _height: u32,
+    _sum: Vec<Vector4<f32>>,
+    _samples: Vec<u32>
+}
+
+impl Film
+{
+    pub fn new(width: u32, height: u32) -> Self
+    {
+        let size = (width * height) as usize;
+        Film
+        {
+            _width: width,
+            _height: height,
+            _sum: vec![Vector4::new(0.0, 0.0, 0.0, 0.0); size],
+            _samples: vec![0; size]
+        }
+    }
+
+    pub fn width(&self) -> u32
+    {
+        self._width
+    }
+
+    pub fn height(&self) -> u32
+    {
+        self._height
+    }
+
+    // Adds the sample sums of a `tile_width` wide block whose top left pixel is (x, y)
+    pub fn add_samples(&mut self, x: u32, y: u32, tile_width: u32, sums: &[Vector4<f32>], samples: u32)
+    {
+        for (k, sum) in sums.iter().enumerate()
+        {
+            let i = x + k as u32 % tile_width;
+            let j = y + k as u32 / tile_width;
+            let index = (i + j * self._width) as usize;
+            self._sum[index] += *sum;
+            self._samples[index] += samples;
+        }
+    }
+
+    // Fewest samples any pixel has received so far
+    pub fn min_samples(&self) -> u32
+    {
+        self._samples.iter().copied().min().unwrap_or(0)
+    }
+
+    // Current average of every pixel, ready for display or `write_image`
+    pub fn resolve(&self) -> Vec<Vector4<f32>>
+    {
+        self._sum.iter().zip(self._samples.iter()).map(|(sum, &n)| resolve_color(*sum, n)).collect()
+    }
+
+    // Current average radiance of every pixel, linear and not clamped
+    pub fn resolve_linear(&self) -> Vec<Vector4<f32>>
+    {
+        self._sum.iter().zip(self._samples.iter()).map(|(sum, &n)| if n == 0 { Vector4::new(0.0, 0.0, 0.0, 1.0) } else { *sum / n as f32 }).collect()
+    }
+
+    // Writes the frame to disk in the format given by the file extension.
+    // OpenEXR keeps the linear radiance, other formats get the display colours.
+    pub fn write(&self, path: &Path) -> io::Result<()>
+    {
+        match path.extension()
+        {
+            Some(ext) if ext.eq_ignore_ascii_case("exr") => write_exr(path, self._width, self._height, &self.resolve_linear()),
+            _ => write_image(path, self._width, self._height, &self.resolve())
+        }
+    }
+}
+
+// Quantize a display-ready [0, 1] channel to 8 bits
+#[inline]
+fn to_byte(x: f32) -> u8
+{
+    let limit = Interval::new(0.0, 1.0);
+    (limit.clamp(x) * 255.0 + 0.5) as u8
+}
+
+pub fn to_rgb8(pixels: &[Vector4<f32>]) -> Vec<u8>
+{
+    let mut bytes = Vec::with_capacity(pixels.len() * 3);
+    for p in pixels
+    {
+        bytes.push(to_byte(p.x));
+        bytes.push(to_byte(p.y));
+        bytes.push(to_byte(p.z));
+    }
+
+    bytes
+}
+
+fn check_size(width: u32, height: u32, pixels: &[Vector4<f32>]) -> io::Result<()>
+{
+    if pixels.len() != (width * height) as usize
+    {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                  format!("expected {} pixels for a {}x{} image, got {}", width * height, width, height, pixels.len())));
+    }
+    Ok(())
+}
+
+// Write display-ready pixels to disk, the format is picked from the file extension (png, ppm, bmp, ...)
+pub fn write_image(path: &Path, width: u32, height: u32, pixels: &[Vector4<f32>]) -> io::Result<()>
+{
+    check_size(width, height, pixels)?;
+    image::save_buffer(path, &to_rgb8(pixels), width, height, image::ColorType::RGB(8))
+}
+
+// Write linear pixels as an OpenEXR file. image 0.22 has no encoder for it,
+// so this writes the simplest valid variant itself: 32-bit float RGB
+// scanlines without compression.
+pub fn write_exr(path: &Path, width: u32, height: u32, pixels: &[Vector4<f32>]) -> io::Result<()>
+{
+    check_size(width, height, pixels)?;
+    let mut out = BufWriter::new(File::create(path)?);
+    encode_exr(&mut out, width, height, pixels)?;
+    out.flush()
+}
+
+fn encode_exr<W: Write>(out: &mut W, width: u32, height: u32, pixels: &[Vector4<f32>]) -> io::Result<()>
+{
+    fn attribute(header: &mut Vec<u8>, name: &str, kind: &str, value: &[u8])
+    {
+        header.extend_from_slice(name.as_bytes());
+        header.push(0);
+        header.extend_from_slice(kind.as_bytes());
+        header.push(0);
+        header.extend_from_slice(&(value.len() as i32).to_le_bytes());
+        header.extend_from_slice(value);
+    }
+
+    // Magic number and version 2, single part scanline file
+    let mut header = vec![0x76, 0x2f, 0x31, 0x01, 2, 0, 0, 0];
+
+    // Channels are stored in alphabetical order, each as FLOAT (2) without subsampling
+    let mut channels = vec![];
+    for name in &["B", "G", "R"]
+    {
+        channels.extend_from_slice(name.as_bytes());
+        channels.push(0);
+        channels.extend_from_slice(&2i32.to_le_bytes());
+        channels.extend_from_slice(&[0, 0, 0, 0]);
+        channels.extend_from_slice(&1i32.to_le_bytes());
+        channels.extend_from_slice(&1i32.to_le_bytes());
+    }
+    channels.push(0);
+    attribute(&mut header, "channels", "chlist", &channels);
+    attribute(&mut header, "compression", "compression", &[0]);
+
+    let window: Vec<u8> = [0, 0, width as i32 - 1, height as i32 - 1].iter().flat_map(|v| v.to_le_bytes().to_vec()).collect();
+    attribute(&mut header, "dataWindow", "box2i", &window);
+    attribute(&mut header, "displayWindow", "box2i", &window);
+    attribute(&mut header, "lineOrder", "lineOrder", &[0]);
+    attribute(&mut header, "pixelAspectRatio", "float", &1.0f32.to_le_bytes());
+    attribute(&mut header, "screenWindowCenter", "v2f", &[0; 8]);
+    attribute(&mut header, "screenWindowWidth", "float", &1.0f32.to_le_bytes());
+    header.push(0);
+    out.write_all(&header)?;
+
+    // Offset table, then one block per scanline: y, byte count and each channel's row
+    let line_bytes = 12 * width as u64;
+    let first_line = header.len() as u64 + 8 * height as u64;
+    for y in 0..height as u64
+    {
+        out.write_all(&(first_line + y * (8 + line_bytes)).to_le_bytes())?;
+    }
+    for (y, row) in pixels.chunks(width as usize).enumerate()
+    {
+        out.write_all(&(y as i32).to_le_bytes())?;
+        out.write_all(&(line_bytes as i32).to_le_bytes())?;
+        for channel in &[2, 1, 0]
+        {
+            for p in row
+            {
+                out.write_all(&p[*channel].to_le_bytes())?;
+            }
+        }
+    }
+    Ok(())
+}
+
+// Read an OpenEXR file as linear RGB pixels, top row first. Only the variant
+// `write_exr` produces and its close relatives are understood: single part
+// scanline files without compression, with half, float or uint channels.
+pub fn read_exr(path: &Path) -> io::Result<(u32, u32, Vec<Vector4<f32>>)>
+{
+    let mut bytes = vec![];
+    File::open(path)?.read_to_end(&mut bytes)?;
+    decode_exr(&bytes)
+}
+
+fn invalid_exr(message: &str) -> io::Error
+{
+    io::Error::new(io::ErrorKind::InvalidData, format!("unsupported OpenEXR file: {}", message))
+}
+
+// IEEE 754 half precision to f32
+fn half_to_f32(bits: u16) -> f32
+{
+    let sign = if bits & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exponent = ((bits >> 10) & 0x1f) as i32;
+    let mantissa = (bits & 0x3ff) as f32;
+    match exponent
+    {
+        0 => sign * mantissa * 2f32.powi(-24),
+        31 if mantissa == 0.0 => sign * f32::INFINITY,
+        31 => f32::NAN,
+        _ => sign * (1.0 + mantissa / 1024.0) * 2f32.powi(exponent - 15)
+    }
+}
+
+fn decode_exr(bytes: &[u8]) -> io::Result<(u32, u32, Vec<Vector4<f32>>)>
+{
+    // Reads `n` bytes at `at` and moves past them
+    fn take<'a>(bytes: &'a [u8], at: &mut usize, n: usize) -> io::Result<&'a [u8]>
+    {
+        let slice = bytes.get(*at..*at + n).ok_or_else(|| invalid_exr("truncated"))?;
+        *at += n;
+        Ok(slice)
+    }
+
+    // Reads a null terminated string at `at` and moves past it
+    fn name(bytes: &[u8], at: &mut usize) -> io::Result<String>
+    {
+        let end = bytes[*at..].iter().position(|&c| c == 0).ok_or_else(|| invalid_exr("truncated"))?;
+        let text = String::from_utf8_lossy(&bytes[*at..*at + end]).into_owned();
+        *at += end + 1;
+        Ok(text)
+    }
+
+    let i32_at = |slice: &[u8], i: usize| i32::from_le_bytes([slice[i], slice[i + 1], slice[i + 2], slice[i + 3]]);
+    let mut at = 0;
+
+    if take(bytes, &mut at, 4)? != [0x76, 0x2f, 0x31, 0x01]
+    {
+        return Err(invalid_exr("not an OpenEXR file"));
+    }
+    // Version 2 without the tiled, long name, deep or multipart flags
+    if take(bytes, &mut at, 4)? != [2, 0, 0, 0]
+    {
+        return Err(invalid_exr("only single part scanline images are supported"));
+    }
+
+    // (name, pixel type) of every channel, in file order
+    let mut channels = vec![];
+    let mut window = None;
+    loop
+    {
+        let attribute = name(bytes, &mut at)?;
+        if attribute.is_empty()
+        {
+            break;
+        }
+        let _kind = name(bytes, &mut at)?;
+        let size = i32_at(take(bytes, &mut at, 4)?, 0);
+        let value = take(bytes, &mut at, size.max(0) as usize)?;
+        match attribute.as_str()
+        {
+            "channels" =>
+            {
+                let mut rest = value;
+                while let Some(end) = rest.iter().position(|&c| c == 0)
+                {
+                    if end == 0
+                    {
+                        break;
+                    }
+                    let channel = String::from_utf8_lossy(&rest[..end]).into_owned();
+                    let info = rest.get(end + 1..end + 17).ok_or_else(|| invalid_exr("truncated channel list"))?;
+                    if i32_at(info, 8) != 1 || i32_at(info, 12) != 1
+                    {
+                        return Err(invalid_exr("subsampled channels"));
+                    }
+                    channels.push((channel, i32_at(info, 0)));
+                    rest = &rest[end + 17..];
+                }
+            }
+            "compression" if value != [0] => return Err(invalid_exr("only uncompressed images are supported")),
+            "dataWindow" if value.len() == 16 => window = Some((i32_at(value, 0), i32_at(value, 4), i32_at(value, 8), i32_at(value, 12))),
+            _ => {}
+        }
+    }
+
+    let (x_min, y_min, x_max, y_max) = window.ok_or_else(|| invalid_exr("no data window"))?;
+    if x_max < x_min || y_max < y_min
+    {
+        return Err(invalid_exr("empty data window"));
+    }
+    let width = (x_max - x_min + 1) as u32;
+    let height = (y_max - y_min + 1) as u32;
+
+    // UINT and FLOAT take 4 bytes, HALF 2
+    let mut line_bytes = 0;
+    for (_, kind) in &channels
+    {
+        line_bytes += match kind
+        {
+            0 | 2 => 4,
+            1 => 2,
+            _ => return Err(invalid_exr("unknown pixel type"))
+        } * width as usize;
+    }
+
+    let mut pixels = vec![Vector4::new(0.0, 0.0, 0.0, 1.0); (width * height) as usize];
+    let table = at;
+    for line in 0..height as usize
+    {
+        let offset = u64::from_le_bytes(bytes.get(table + 8 * line..table + 8 * line + 8).ok_or_else(|| invalid_exr("truncated offset table"))?.try_into().unwrap()) as usize;
+        let block = bytes.get(offset..offset + 8 + line_bytes).ok_or_else(|| invalid_exr("truncated scanline"))?;
+        let y = i32_at(block, 0) - y_min;
+        if y < 0 || y >= height as i32 || i32_at(block, 4) as usize != line_bytes
+        {
+            return Err(invalid_exr("bad scanline block"));
+        }
+
+        let mut data = &block[8..];
+        for (channel, kind) in &channels
+        {
+            let component = match channel.as_str()
+            {
+                "R" => Some(0),
+                "G" => Some(1),
+                "B" => Some(2),
+                "A" => Some(3),
+                _ => None
+            };
+            for x in 0..width as usize
+            {
+                let value = match kind
+                {
+                    0 => u32::from_le_bytes(data[4 * x..4 * x + 4].try_into().unwrap()) as f32,
+                    1 => half_to_f32(u16::from_le_bytes([data[2 * x], data[2 * x + 1]])),
+                    _ => f32::from_le_bytes(data[4 * x..4 * x + 4].try_into().unwrap())
+                };
+                if let Some(c) = component
+                {
+                    pixels[x + y as usize * width as usize][c] = value;
+                }
+            }
+            data = &data[if *kind == 1 { 2 } else { 4 } * width as usize..];
+        }
+    }
+    Ok((width, height, pixels))
+}
+
+#[cfg(test)]
+mod test
+{
+    use cgmath::Array;
+    use super::*;
+
+    #[test]
+    fn test_to_rgb8()
+    {
+        let pixels = vec![Vector4::new(0.0, 0.5, 1.0, 1.0), Vector4::new(-1.0, 2.0, 1.0, 1.0)];
+        assert_eq!(to_rgb8(&pixels), vec![0, 128, 255, 0, 255, 255]);
+    }
+
+    #[test]
+    fn test_film_accumulates()
+    {
+        let mut film = Film::new(2, 2);
+        film.add_samples(1, 0, 1, &[Vector4::new(0.25, 1.0, 4.0, 1.0)], 1);
+        film.add_samples(1, 0, 1, &[Vector4::new(0.25, 1.0, 0.0, 1.0)], 1);
+
+        let pixels = film.resolve();
+        assert_eq!(pixels[1], Vector4::new(0.5, 1.0, 1.0, 1.0));
+        assert_eq!(pixels[0], Vector4::new(0.0, 0.0, 0.0, 1.0));
+        assert_eq!(film.min_samples(), 0);
+    }
+
+    #[test]
+    fn test_write_image_size_mismatch()
+    {
+        let pixels = vec![Vector4::from_value(1.0); 3];
+        assert!(write_image(Path::new("mismatch.png"), 2, 2, &pixels).is_err());
+    }
+
+    #[test]
+    fn test_exr_layout()
+    {
+        let pixels = vec![Vector4::new(0.5, 2.0, 8.0, 1.0), Vector4::new(1.0, 0.0, 0.0, 1.0)];
+        let mut bytes = vec![];
+        encode_exr(&mut bytes, 1, 2, &pixels).unwrap();
+        assert_eq!(&bytes[..8], &[0x76, 0x2f, 0x31, 0x01, 2, 0, 0, 0]);
+
+        // The offset table follows the header and points at each scanline
+        let line = |y: usize| u64::from_le_bytes(bytes[bytes.len() - 2 * 20 - 16 + 8 * y..][..8].try_into().unwrap()) as usize;
+        let float = |at: usize| f32::from_le_bytes(bytes[at..at + 4].try_into().unwrap());
+        assert_eq!(line(0), bytes.len() - 2 * 20);
+        assert_eq!(line(1), bytes.len() - 20);
+
+        // Scanline 1 holds y, the byte count, then B, G and R, values kept above 1
+        assert_eq!(i32::from_le_bytes(bytes[line(1)..line(1) + 4].try_into().unwrap()), 1);
+        assert_eq!((float(line(0) + 8), float(line(0) + 12), float(line(0) + 16)), (8.0, 2.0, 0.5));
+        assert_eq!(float(line(1) + 16), 1.0);
+    }
+
+    #[test]
+    fn test_exr_round_trip()
+    {
+        let pixels = vec![Vector4::new(0.5, 2.0, 8.0, 1.0), Vector4::new(1.0, 0.0, -3.0, 1.0)];
+        let mut bytes = vec![];
+        encode_exr(&mut bytes, 2, 1, &pixels).unwrap();
+        assert_eq!(decode_exr(&bytes).unwrap(), (2, 1, pixels));
+
+        assert!(decode_exr(&bytes[..bytes.len() - 1]).is_err());
+        assert!(decode_exr(b"P6 1 1 255").is_err());
+    }
+
+    #[test]
+    fn test_half_to_f32()
+    {
+        assert_eq!(half_to_f32(0x3c00), 1.0);
+        assert_eq!(half_to_f32(0xc000), -2.0);
+        assert_eq!(half_to_f32(0x0001), 2f32.powi(-24));
+        assert_eq!(half_to_f32(0x7c00), f32::INFINITY);
+    }
+}
\ No newline at end of file
@@ -1,5 +1,7 @@
 use std::sync::Arc;
-use cgmath::{Vector3};
+use cgmath::{dot, Vector3};
+use crate::renderer::aabb::Aabb;
+use crate::renderer::custom_function::{length, length_squared};
 use crate::renderer::interval::Interval;
 use crate::renderer::material::Material;
 use crate::renderer::ray::Ray;
@@ -11,6 +13,9 @@ pub struct HitRecord
     pub _normal: Vector3<f32>,
     pub _material: Arc<dyn Material>,
     pub _t: f32,
+    // Surface coordinates of the hit, used for texture lookups
+    pub _u: f32,
+    pub _v: f32,
     pub _front_face: bool
 }
 
@@ -47,8 +52,91 @@ impl HitRecord
     }
 }
 
-pub trait Hittable
+pub trait Hittable: Sync + Send
 {
     //fn hit(&self, ray: Ray, ray_t: Interval, rec: &mut HitRecord) -> bool;
     fn hit(&self, ray: Ray, ray_t: Interval) -> Option<HitRecord>;
-}
\ No newline at end of file
+
+    fn bounding_box(&self) -> Aabb;
+
+    // Light sampling: density, per unit solid angle, of `random` returning `direction`
+    // from `origin`. Zero for objects that can't be sampled this way.
+    fn pdf_value(&self, _origin: Vector3<f32>, _direction: Vector3<f32>) -> f32
+    {
+        0.0
+    }
+
+    // Direction from `origin` towards a random point on the object
+    fn random(&self, _origin: Vector3<f32>) -> Vector3<f32>
+    {
+        Vector3::new(1.0, 0.0, 0.0)
+    }
+}
+
+// `pdf_value` for a point picked uniformly over the `area` of a surface. Every
+// crossing of the ray counts, so closed and curved shapes, which can hide part
+// of themselves, sample correctly. Needs hits to report geometric normals.
+pub fn area_pdf_value(object: &dyn Hittable, area: f32, origin: Vector3<f32>, direction: Vector3<f32>) -> f32
+{
+    let ray = Ray::new(origin, direction);
+    let mut pdf = 0.0;
+    let mut t_min = 0.001;
+    while let Some(rec) = object.hit(ray, Interval::new(t_min, f32::INFINITY))
+    {
+        let distance_squared = rec._t * rec._t * length_squared(direction);
+        let cosine = (dot(direction, rec._normal) / length(direction)).abs();
+        pdf += distance_squared / (cosine * area);
+        t_min = rec._t + 0.001;
+    }
+    pdf
+}
+
+#[cfg(test)]
+mod test
+{
+    use cgmath::Array;
+    use super::*;
+    use crate::renderer::custom_function::random_unit_vector3;
+    use crate::renderer::cylinder::{Cone, Cylinder};
+    use crate::renderer::material::DiffuseLight;
+    use crate::renderer::mesh::{Mesh, MeshData};
+    use crate::renderer::sampler::seed_thread_sampler;
+    use crate::renderer::triangle::Triangle;
+    use crate::utility::constants::PI;
+
+    #[test]
+    fn test_area_lights_are_proper_densities()
+    {
+        seed_thread_sampler(3, 0, 0);
+        let light: Arc<dyn Material> = Arc::new(DiffuseLight::new(Vector3::from_value(1.0)));
+
+        // A closed tetrahedron with smooth normals, which a light must ignore
+        let corners = vec![Vector3::new(1.0, 1.0, 1.0), Vector3::new(1.0, -1.0, -1.0), Vector3::new(-1.0, 1.0, -1.0), Vector3::new(-1.0, -1.0, 1.0)];
+        let tetrahedron = MeshData
+        {
+            normals: Some(corners.iter().map(|&c| c / 3.0f32.sqrt()).collect()),
+            positions: corners,
+            texcoords: None,
+            indices: vec![0, 1, 2, 0, 3, 1, 0, 2, 3, 1, 3, 2]
+        };
+
+        let eye = Vector3::new(0.0, 0.0, 2.0);
+        let lights: [(&str, Arc<dyn Hittable>); 4] = [
+            ("triangle", Arc::new(Triangle::new(Vector3::new(-2.0, -2.0, -0.5), Vector3::new(2.0, -2.0, -0.5), Vector3::new(0.0, 2.0, -0.5), light.clone()))),
+            ("mesh", Arc::new(Mesh::new(tetrahedron, light.clone()))),
+            ("cylinder", Arc::new(Cylinder::new(Vector3::new(1.0, -2.0, 0.0), Vector3::new(1.0, 2.0, 0.0), 0.5, light.clone()))),
+            ("cone", Arc::new(Cone::new(Vector3::new(0.0, -0.5, 0.0), Vector3::new(0.0, -2.0, 0.0), 1.5, light.clone())))];
+        for (name, object) in lights.iter()
+        {
+            // Directions picked by `random` have a density, and over all directions it sums to one
+            for _ in 0..100
+            {
+                assert!(object.pdf_value(eye, object.random(eye)) > 0.0, "{}", name);
+            }
+            let samples = 200_000;
+            let total: f32 = (0..samples).map(|_| object.pdf_value(eye, random_unit_vector3())).sum();
+            let integral = total * 4.0 * PI / samples as f32;
+            assert!((integral - 1.0).abs() < 0.03, "{}: {}", name, integral);
+        }
+    }
+}
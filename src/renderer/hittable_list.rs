@@ -1,4 +1,7 @@
-use std::rc::Rc;
+use std::sync::Arc;
+use cgmath::Vector3;
+use crate::renderer::aabb::Aabb;
+use crate::renderer::custom_function::random_int;
 use crate::renderer::hittable::{HitRecord, Hittable};
 use crate::renderer::interval::Interval;
 use crate::renderer::ray::Ray;
@@ -6,7 +9,8 @@ use crate::renderer::ray::Ray;
 #[derive(Clone)]
 pub struct HittableList
 {
-    pub objects: Vec<Rc<dyn Hittable>>
+    pub objects: Vec<Arc<dyn Hittable>>,
+    bbox: Aabb
 }
 
 impl Hittable for HittableList
@@ -46,6 +50,33 @@ impl Hittable for HittableList
 
         hit_anything
     }
+
+    fn bounding_box(&self) -> Aabb
+    {
+        self.bbox
+    }
+
+    // Each object is picked with equal probability
+    fn pdf_value(&self, origin: Vector3<f32>, direction: Vector3<f32>) -> f32
+    {
+        if self.objects.is_empty()
+        {
+            return 0.0;
+        }
+
+        let sum: f32 = self.objects.iter().map(|object| object.pdf_value(origin, direction)).sum();
+        sum / self.objects.len() as f32
+    }
+
+    fn random(&self, origin: Vector3<f32>) -> Vector3<f32>
+    {
+        if self.objects.is_empty()
+        {
+            return Vector3::new(1.0, 0.0, 0.0);
+        }
+
+        self.objects[random_int(0, self.objects.len() - 1)].random(origin)
+    }
 }
 
 impl HittableList
@@ -54,18 +85,21 @@ impl HittableList
     {
         HittableList
         {
-            objects: vec![]
+            objects: vec![],
+            bbox: Aabb::empty()
         }
     }
 
-    pub fn add(&mut self, object: Rc<dyn Hittable>)
+    pub fn add(&mut self, object: Arc<dyn Hittable>)
     {
+        self.bbox = Aabb::surrounding(&self.bbox, &object.bounding_box());
         self.objects.push(object)
     }
 
     pub fn clear(&mut self)
     {
-        self.objects.clear()
+        self.objects.clear();
+        self.bbox = Aabb::empty();
     }
 }
 
@@ -1,5 +1,6 @@
 use crate::utility::constants::INFINITY;
 
+#[derive(Copy, Clone, Debug)]
 pub struct Interval
 {
     _min: f32,
@@ -17,6 +18,31 @@ impl Interval
         }
     }
 
+    // The tightest interval enclosing both `a` and `b`
+    pub fn enclosing(a: Interval, b: Interval) -> Self
+    {
+        Interval
+        {
+            _min: a._min.min(b._min),
+            _max: a._max.max(b._max)
+        }
+    }
+
+    pub fn size(&self) -> f32
+    {
+        self._max - self._min
+    }
+
+    pub fn expand(&self, delta: f32) -> Self
+    {
+        let padding = delta / 2.0;
+        Interval
+        {
+            _min: self._min - padding,
+            _max: self._max + padding
+        }
+    }
+
     pub fn contains(&self, x: f32) -> bool
     {
         self._min <= x && self._max >= x
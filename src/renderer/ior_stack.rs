@@ -0,0 +1,120 @@
+// Nested dielectrics (Schmidt and Budge 2002). Every path keeps the media it is
+// inside of; where closed objects overlap, the one with the highest priority is
+// the medium actually there and surfaces of the others are passed through.
+// This is what lets water sit in a glass with their surfaces overlapping, or an
+// air bubble inside a glass ball, without negative radii.
+
+use cgmath::Vector3;
+
+// The inside of a closed object bounded by a dielectric surface
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Interior
+{
+    pub ior: f32,
+    pub priority: u32,
+    // Beer-Lambert coefficient, per unit distance and channel
+    pub absorption: Vector3<f32>
+}
+
+// Deeper nesting than this is not tracked, further media are treated as air
+const CAPACITY: usize = 8;
+
+// Media a path is currently inside of, each tagged with the identity of the
+// material it came from so leaving removes the right one
+#[derive(Copy, Clone, Debug)]
+pub struct IorStack
+{
+    _entries: [(usize, Interior); CAPACITY],
+    _len: usize
+}
+
+impl IorStack
+{
+    // Paths start outside everything, in air
+    pub fn new() -> Self
+    {
+        IorStack
+        {
+            _entries: [(0, Interior { ior: 1.0, priority: 0, absorption: Vector3::new(0.0, 0.0, 0.0) }); CAPACITY],
+            _len: 0
+        }
+    }
+
+    // The medium the path is in, `None` means air
+    pub fn current(&self) -> Option<Interior>
+    {
+        self.highest(None)
+    }
+
+    // The medium on the other side of a surface of material `id`, what is left
+    // after leaving one instance of it
+    pub fn surrounding(&self, id: usize) -> Option<Interior>
+    {
+        self.highest(self._entries[..self._len].iter().rposition(|(entry, _)| *entry == id))
+    }
+
+    // Highest priority entry apart from the one at `skip`, the latest on ties
+    fn highest(&self, skip: Option<usize>) -> Option<Interior>
+    {
+        let mut best: Option<Interior> = None;
+        for (i, (_, interior)) in self._entries[..self._len].iter().enumerate()
+        {
+            if Some(i) != skip && best.is_none_or(|b| interior.priority >= b.priority)
+            {
+                best = Some(*interior);
+            }
+        }
+        best
+    }
+
+    pub fn enter(&mut self, id: usize, interior: Interior)
+    {
+        if self._len < CAPACITY
+        {
+            self._entries[self._len] = (id, interior);
+            self._len += 1;
+        }
+    }
+
+    // Leaving a medium that was never entered, e.g. a camera placed inside glass, does nothing
+    pub fn leave(&mut self, id: usize)
+    {
+        if let Some(i) = self._entries[..self._len].iter().rposition(|(entry, _)| *entry == id)
+        {
+            self._entries.copy_within(i + 1..self._len, i);
+            self._len -= 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test
+{
+    use super::*;
+
+    #[test]
+    fn test_surrounding_medium()
+    {
+        let glass = Interior { ior: 1.5, priority: 1, absorption: Vector3::new(0.0, 0.0, 0.0) };
+        let water = Interior { ior: 1.33, priority: 0, absorption: Vector3::new(0.1, 0.0, 0.0) };
+        let mut stack = IorStack::new();
+        assert_eq!(stack.surrounding(1), None);
+
+        // Inside the glass wall where the water overlaps it, glass wins either way
+        stack.enter(1, glass);
+        stack.enter(2, water);
+        assert_eq!(stack.surrounding(2), Some(glass));
+        assert_eq!(stack.surrounding(1), Some(water));
+        assert_eq!(stack.current(), Some(glass));
+
+        // Past the wall only the water is left
+        stack.leave(1);
+        assert_eq!(stack.current(), Some(water));
+        assert_eq!(stack.surrounding(2), None);
+        assert_eq!(stack.surrounding(1), Some(water));
+
+        stack.leave(2);
+        stack.leave(2);
+        assert_eq!(stack.surrounding(3), None);
+    }
+}
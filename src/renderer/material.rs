@@ -1,77 +1,145 @@
+use std::sync::Arc;
 use cgmath::{Array, dot, Vector3};
 use cgmath::num_traits::pow;
 use crate::renderer::custom_function::{near_zero, random_double, random_unit_vector3, reflect, refract, unit_vector3};
 use crate::renderer::hittable::HitRecord;
+use crate::renderer::ior_stack::Interior;
+use crate::renderer::microfacet::{distinct_eta, fresnel_conductor, fresnel_dielectric, reflect_about, refract_about, Ggx, ShadingFrame};
+use crate::renderer::pdf::{CosinePdf, Pdf, SpherePdf};
 use crate::renderer::ray::Ray;
+use crate::renderer::spectrum::{Dispersion, D_LINE};
+use crate::renderer::texture::{SolidColor, Texture};
 
 pub trait Material: Sync + Send
 {
     fn scatter(&self, r_in: &Ray, rec: &HitRecord, attenuation: &mut Vector3<f32>, scattered: &mut Ray) -> bool;
+
+    // Radiance emitted at the hit point, black for everything but lights
+    fn emitted(&self, _u: f32, _v: f32, _p: Vector3<f32>) -> Vector3<f32>
+    {
+        Vector3::from_value(0.0)
+    }
+
+    // Density, per unit solid angle, of `scatter` picking `scattered`. A material
+    // that returns non-zero here must sample `scatter` from exactly this density,
+    // which lets the camera weigh its bounces against light sampling. The default
+    // of zero marks specular materials that scatter into a single direction.
+    fn scattering_pdf(&self, _r_in: &Ray, _rec: &HitRecord, _scattered: &Ray) -> f32
+    {
+        0.0
+    }
+
+    // BSDF times the cosine at the surface, the fraction of light arriving along
+    // `scattered` that leaves back along `r_in`. For materials with a density the
+    // attenuation `scatter` returns must equal this over `scattering_pdf`.
+    fn eval(&self, _r_in: &Ray, _rec: &HitRecord, _scattered: &Ray) -> Vector3<f32>
+    {
+        Vector3::from_value(0.0)
+    }
+
+    // Whether objects with this material are worth sampling as lights
+    fn is_emissive(&self) -> bool
+    {
+        false
+    }
+
+    // Whether `scatter` depends on the wavelength of `r_in`, spectral paths only
+    // keep their hero wavelength after such a bounce
+    fn is_dispersive(&self) -> bool
+    {
+        false
+    }
+
+    // The medium inside closed objects with this material, `None` for surfaces
+    // that don't bound one. Such surfaces take part in the camera's `IorStack`
+    // and read the index beyond them from `Ray::surrounding_ior`.
+    fn interior(&self, _wavelength: Option<f32>) -> Option<Interior>
+    {
+        None
+    }
 }
 
 
 #[derive(Clone)]
 pub struct Lambertian
 {
-    albedo: Vector3<f32>
+    albedo: Arc<dyn Texture>
 }
 
 impl Lambertian
 {
     pub fn new(color: Vector3<f32>) -> Self
+    {
+        Lambertian::from_texture(Arc::new(SolidColor::new(color)))
+    }
+
+    pub fn from_texture(texture: Arc<dyn Texture>) -> Self
     {
         Lambertian
         {
-            albedo: color
+            albedo: texture
         }
     }
 
-    pub fn get_albedo(&self) -> Vector3<f32>
+    pub fn get_albedo(&self, u: f32, v: f32, p: Vector3<f32>) -> Vector3<f32>
     {
-        self.albedo
+        self.albedo.value(u, v, p)
     }
-
-
 }
 
 impl Material for Lambertian
 {
     fn scatter(&self, r_in: &Ray, rec: &HitRecord, attenuation: &mut Vector3<f32>, scattered: &mut Ray) -> bool
     {
-        let mut scatter_direction = rec.get_normal() + random_unit_vector3();
+        let mut scatter_direction = CosinePdf::new(rec.get_normal()).generate();
         if near_zero(scatter_direction)
         {
             scatter_direction = rec.get_normal();
         }
-        let r = Ray::new(rec.get_point(), scatter_direction);
+        let r = Ray::with_time(rec.get_point(), scatter_direction, r_in.time());
         scattered.clone_from(&r);
-        let albedo = self.get_albedo();
+        let albedo = self.get_albedo(rec._u, rec._v, rec.get_point());
         attenuation.clone_from(&albedo);
         true
     }
+
+    fn scattering_pdf(&self, _r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f32
+    {
+        CosinePdf::new(rec.get_normal()).value(scattered.direction())
+    }
+
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Vector3<f32>
+    {
+        self.get_albedo(rec._u, rec._v, rec.get_point()) * self.scattering_pdf(r_in, rec, scattered)
+    }
 }
 
 #[derive(Clone)]
 pub struct Metal
 {
-    albedo: Vector3<f32>,
+    albedo: Arc<dyn Texture>,
     fuzz: f32
 }
 
 impl Metal
 {
     pub fn new(v: Vector3<f32>, f: f32) -> Self
+    {
+        Metal::from_texture(Arc::new(SolidColor::new(v)), f)
+    }
+
+    pub fn from_texture(texture: Arc<dyn Texture>, f: f32) -> Self
     {
         Metal
         {
-            albedo: v,
+            albedo: texture,
             fuzz: f.min(1.0)
         }
     }
 
-    pub fn get_albedo(&self) -> Vector3<f32>
+    pub fn get_albedo(&self, u: f32, v: f32, p: Vector3<f32>) -> Vector3<f32>
     {
-        self.albedo
+        self.albedo.value(u, v, p)
     }
 
     pub fn get_fuzz(&self) -> f32
@@ -85,18 +153,31 @@ impl Material for Metal
     fn scatter(&self, r_in: &Ray, rec: &HitRecord, attenuation: &mut Vector3<f32>, scattered: &mut Ray) -> bool
     {
         let reflected = reflect(unit_vector3(r_in.direction()), rec.get_normal());
-        let r = Ray::new(rec.get_point(), reflected + self.fuzz * random_unit_vector3());
+        let r = Ray::with_time(rec.get_point(), reflected + self.fuzz * random_unit_vector3(), r_in.time());
         scattered.clone_from(&r);
-        let albedo = self.get_albedo();
+        let albedo = self.get_albedo(rec._u, rec._v, rec.get_point());
         attenuation.clone_from(&albedo);
         dot(scattered.direction(), rec.get_normal()) > 0.0
     }
 }
 
+// Absorption given as the colour white light takes on after `distance` inside
+pub fn absorption_from_tint(color: Vector3<f32>, distance: f32) -> Vector3<f32>
+{
+    color.map(|c| -c.clamp(1e-6, 1.0).ln() / distance.max(1e-6))
+}
+
 #[derive(Clone)]
 pub struct Dielectric
 {
-    ior: f32
+    // Used as is by RGB paths, spectral ones follow `dispersion` when there is one
+    ior: f32,
+    dispersion: Option<Dispersion>,
+    // Fraction of light absorbed per unit distance inside, per channel (Beer-Lambert).
+    // The camera applies it to every stretch of path inside, see `interior`.
+    absorption: Vector3<f32>,
+    // Where objects overlap the one with the highest priority is the medium there
+    priority: u32
 }
 
 impl Dielectric
@@ -105,15 +186,74 @@ impl Dielectric
     {
         Dielectric
         {
-            ior: ior
+            ior,
+            dispersion: None,
+            absorption: Vector3::from_value(0.0),
+            priority: 0
+        }
+    }
+
+    // Glass whose ior varies with wavelength, RGB renders see its d-line value
+    pub fn dispersive(dispersion: Dispersion) -> Self
+    {
+        Dielectric
+        {
+            ior: dispersion.ior(D_LINE),
+            dispersion: Some(dispersion),
+            absorption: Vector3::from_value(0.0),
+            priority: 0
+        }
+    }
+
+    // Nesting priority, give water in a glass a lower one than the glass and an
+    // air bubble (ior 1) a higher one than whatever it is in
+    pub fn with_priority(mut self, priority: u32) -> Self
+    {
+        self.priority = priority;
+        self
+    }
+
+    // Absorption coefficient of the inside, light travelling a distance d keeps exp(-absorption * d)
+    pub fn absorbing(mut self, absorption: Vector3<f32>) -> Self
+    {
+        self.absorption = absorption.map(|a| a.max(0.0));
+        self
+    }
+
+    // Named glass, see `Dispersion::glass`
+    pub fn glass(name: &str) -> Option<Self>
+    {
+        Dispersion::glass(name).map(Dielectric::dispersive)
+    }
+
+    fn ior_at(&self, wavelength: Option<f32>) -> f32
+    {
+        match (self.dispersion, wavelength)
+        {
+            (Some(dispersion), Some(lambda)) => dispersion.ior(lambda),
+            _ => self.ior
         }
     }
 
-    pub fn reflectance(cosine: f32, ref_idx: f32) -> f32
+    // Schlick's approximation for light going from index `n1` into `n2` at `cosine`
+    // to the normal. Into a less dense medium the curve has to be evaluated at the
+    // angle on the denser side, the transmitted one.
+    pub fn reflectance(cosine: f32, n1: f32, n2: f32) -> f32
     {
-        let mut r0 = (1.0 - ref_idx) / (1.0 + ref_idx);
+        let mut r0 = (n1 - n2) / (n1 + n2);
         r0 = r0 * r0;
-        r0 + (1.0 - r0) * pow((1.0 - cosine), 5)
+
+        let mut cosine = cosine;
+        if n1 > n2
+        {
+            let sin2_t = (n1 / n2) * (n1 / n2) * (1.0 - cosine * cosine);
+            if sin2_t > 1.0
+            {
+                return 1.0;
+            }
+            cosine = (1.0 - sin2_t).sqrt();
+        }
+        r0 + (1.0 - r0) * pow(1.0 - cosine, 5)
     }
 }
 
@@ -121,15 +261,11 @@ impl Material for Dielectric
 {
     fn scatter(&self, r_in: &Ray, rec: &HitRecord, attenuation: &mut Vector3<f32>, scattered: &mut Ray) -> bool {
         attenuation.clone_from(&Vector3::from_value(1.0));
-        let mut refraction_ratio : f32;
-        if rec._front_face
-        {
-            refraction_ratio = 1.0 / self.ior;
-        }
-        else
-        {
-            refraction_ratio = self.ior;
-        }
+
+        // Indices on the incoming and the far side of the surface
+        let ior = self.ior_at(r_in.wavelength());
+        let (n1, n2) = if rec._front_face { (r_in.surrounding_ior(), ior) } else { (ior, r_in.surrounding_ior()) };
+        let refraction_ratio = n1 / n2;
 
         let unit_direction = unit_vector3(r_in.direction());
         let cos_theta = dot(-unit_direction, rec.get_normal()).min(1.0);
@@ -138,7 +274,7 @@ impl Material for Dielectric
         let cannot_refract = refraction_ratio * sin_theta > 1.0;
         let mut direction: Vector3<f32>;
 
-        if cannot_refract || Dielectric::reflectance(cos_theta.clone(), refraction_ratio.clone()) > random_double()
+        if cannot_refract || Dielectric::reflectance(cos_theta, n1, n2) > random_double()
         {
             direction = reflect(unit_direction, rec.get_normal());
         }
@@ -147,7 +283,404 @@ impl Material for Dielectric
             direction = refract(unit_direction, rec.get_normal(), refraction_ratio);
         }
 
-        scattered.clone_from(&Ray::new(rec.get_point(), direction));
+        scattered.clone_from(&Ray::with_time(rec.get_point(), direction, r_in.time()));
         true
     }
+
+    fn is_dispersive(&self) -> bool
+    {
+        self.dispersion.is_some()
+    }
+
+    fn interior(&self, wavelength: Option<f32>) -> Option<Interior>
+    {
+        Some(Interior { ior: self.ior_at(wavelength), priority: self.priority, absorption: self.absorption })
+    }
+}
+
+#[derive(Clone)]
+pub struct DiffuseLight
+{
+    emit: Arc<dyn Texture>
+}
+
+impl DiffuseLight
+{
+    pub fn new(emit: Vector3<f32>) -> Self
+    {
+        DiffuseLight::from_texture(Arc::new(SolidColor::new(emit)))
+    }
+
+    pub fn from_texture(texture: Arc<dyn Texture>) -> Self
+    {
+        DiffuseLight
+        {
+            emit: texture
+        }
+    }
+}
+
+impl Material for DiffuseLight
+{
+    fn scatter(&self, _r_in: &Ray, _rec: &HitRecord, _attenuation: &mut Vector3<f32>, _scattered: &mut Ray) -> bool
+    {
+        false
+    }
+
+    fn emitted(&self, u: f32, v: f32, p: Vector3<f32>) -> Vector3<f32>
+    {
+        self.emit.value(u, v, p)
+    }
+
+    fn is_emissive(&self) -> bool
+    {
+        true
+    }
+}
+
+
+// Phase function of a participating medium that scatters equally in all directions
+#[derive(Clone)]
+pub struct Isotropic
+{
+    albedo: Arc<dyn Texture>
+}
+
+impl Isotropic
+{
+    #[cfg(test)]
+    pub fn new(albedo: Vector3<f32>) -> Self
+    {
+        Isotropic::from_texture(Arc::new(SolidColor::new(albedo)))
+    }
+
+    pub fn from_texture(texture: Arc<dyn Texture>) -> Self
+    {
+        Isotropic
+        {
+            albedo: texture
+        }
+    }
+}
+
+impl Material for Isotropic
+{
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, attenuation: &mut Vector3<f32>, scattered: &mut Ray) -> bool
+    {
+        scattered.clone_from(&Ray::with_time(rec.get_point(), SpherePdf.generate(), r_in.time()));
+        attenuation.clone_from(&self.albedo.value(rec._u, rec._v, rec.get_point()));
+        true
+    }
+
+    fn scattering_pdf(&self, _r_in: &Ray, _rec: &HitRecord, scattered: &Ray) -> f32
+    {
+        SpherePdf.value(scattered.direction())
+    }
+
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Vector3<f32>
+    {
+        self.albedo.value(rec._u, rec._v, rec.get_point()) * self.scattering_pdf(r_in, rec, scattered)
+    }
+}
+
+// View and light directions of a bounce in the shading frame of the hit, both
+// pointing away from the surface
+fn local_directions(r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> (Vector3<f32>, Vector3<f32>)
+{
+    let frame = ShadingFrame::new(rec.get_normal());
+    (frame.to_local(-unit_vector3(r_in.direction())), frame.to_local(unit_vector3(scattered.direction())))
+}
+
+// Complex index of refraction (eta, k) at roughly 650, 550 and 450 nm for the
+// metals the scene format knows by name
+pub fn metal_ior(name: &str) -> Option<(Vector3<f32>, Vector3<f32>)>
+{
+    match name
+    {
+        "gold" => Some((Vector3::new(0.143, 0.374, 1.442), Vector3::new(3.983, 2.385, 1.603))),
+        "copper" => Some((Vector3::new(0.200, 0.924, 1.102), Vector3::new(3.912, 2.452, 2.142))),
+        "aluminium" => Some((Vector3::new(1.657, 0.880, 0.521), Vector3::new(9.224, 6.270, 4.837))),
+        _ => None
+    }
+}
+
+// Rough metal as a GGX (Cook-Torrance) microfacet surface, coloured only by the
+// Fresnel reflectance of its complex index of refraction `eta + i k`
+#[derive(Clone)]
+pub struct Conductor
+{
+    eta: Vector3<f32>,
+    k: Vector3<f32>,
+    distribution: Ggx
+}
+
+impl Conductor
+{
+    pub fn new(eta: Vector3<f32>, k: Vector3<f32>, roughness: f32, anisotropy: f32) -> Self
+    {
+        Conductor
+        {
+            eta,
+            k,
+            distribution: Ggx::new(roughness, anisotropy)
+        }
+    }
+
+    // One of the metals known to `metal_ior`
+    pub fn preset(name: &str, roughness: f32, anisotropy: f32) -> Option<Self>
+    {
+        let (eta, k) = metal_ior(name)?;
+        Some(Conductor::new(eta, k, roughness, anisotropy))
+    }
+
+    fn eval_local(&self, wo: Vector3<f32>, wi: Vector3<f32>) -> Vector3<f32>
+    {
+        if wo.z <= 0.0 || wi.z <= 0.0
+        {
+            return Vector3::from_value(0.0);
+        }
+        let h = unit_vector3(wo + wi);
+        let fresnel = fresnel_conductor(dot(wo, h), self.eta, self.k);
+        fresnel * (self.distribution.d(h) * self.distribution.g(wo, wi) / (4.0 * wo.z))
+    }
+
+    fn pdf_local(&self, wo: Vector3<f32>, wi: Vector3<f32>) -> f32
+    {
+        if wo.z <= 0.0 || wi.z <= 0.0
+        {
+            return 0.0;
+        }
+        let h = unit_vector3(wo + wi);
+        self.distribution.visible_pdf(wo, h) / (4.0 * dot(wo, h))
+    }
+}
+
+impl Material for Conductor
+{
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, attenuation: &mut Vector3<f32>, scattered: &mut Ray) -> bool
+    {
+        let frame = ShadingFrame::new(rec.get_normal());
+        let wo = frame.to_local(-unit_vector3(r_in.direction()));
+        if wo.z <= 0.0
+        {
+            return false;
+        }
+
+        let h = self.distribution.sample_visible(wo, random_double(), random_double());
+        let wi = reflect_about(wo, h);
+        let pdf = self.pdf_local(wo, wi);
+        // Reflected below the surface, the single-scattering model drops that light
+        if pdf <= 0.0
+        {
+            return false;
+        }
+
+        scattered.clone_from(&Ray::with_time(rec.get_point(), frame.to_world(wi), r_in.time()));
+        attenuation.clone_from(&(self.eval_local(wo, wi) / pdf));
+        true
+    }
+
+    fn scattering_pdf(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f32
+    {
+        let (wo, wi) = local_directions(r_in, rec, scattered);
+        self.pdf_local(wo, wi)
+    }
+
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Vector3<f32>
+    {
+        let (wo, wi) = local_directions(r_in, rec, scattered);
+        self.eval_local(wo, wi)
+    }
+}
+
+// Frosted glass: GGX microfacets that reflect or refract by the dielectric
+// Fresnel term (Walter et al. 2007). Colourless like `Dielectric`.
+#[derive(Clone)]
+pub struct RoughDielectric
+{
+    ior: f32,
+    distribution: Ggx,
+    // Interior of the object, as for `Dielectric`
+    absorption: Vector3<f32>,
+    priority: u32
+}
+
+impl RoughDielectric
+{
+    pub fn new(ior: f32, roughness: f32, anisotropy: f32) -> Self
+    {
+        RoughDielectric
+        {
+            ior,
+            distribution: Ggx::new(roughness, anisotropy),
+            absorption: Vector3::from_value(0.0),
+            priority: 0
+        }
+    }
+
+    // See `Dielectric::with_priority`
+    pub fn with_priority(mut self, priority: u32) -> Self
+    {
+        self.priority = priority;
+        self
+    }
+
+    // See `Dielectric::absorbing`
+    pub fn absorbing(mut self, absorption: Vector3<f32>) -> Self
+    {
+        self.absorption = absorption.map(|a| a.max(0.0));
+        self
+    }
+
+    // Ratio of the index behind the surface to the one in front of it, against
+    // the medium beyond as reported by the ray
+    fn eta(&self, r_in: &Ray, rec: &HitRecord) -> f32
+    {
+        let eta = if rec._front_face { self.ior / r_in.surrounding_ior() } else { r_in.surrounding_ior() / self.ior };
+        distinct_eta(eta)
+    }
+
+    // Microfacet normal that turns `wo` into `wi`, with the Fresnel reflectance
+    // there and, for refraction, the denominator of the change of variables
+    fn half_vector(&self, wo: Vector3<f32>, wi: Vector3<f32>, eta: f32) -> Option<(Vector3<f32>, f32, Option<f32>)>
+    {
+        if wo.z <= 0.0 || wi.z == 0.0
+        {
+            return None;
+        }
+
+        if wi.z > 0.0
+        {
+            let h = unit_vector3(wo + wi);
+            return Some((h, fresnel_dielectric(dot(wo, h), eta), None));
+        }
+
+        let mut h = unit_vector3(wo + eta * wi);
+        if h.z < 0.0
+        {
+            h = -h;
+        }
+        // Both directions must see the microfacet from opposite sides
+        if dot(wo, h) <= 0.0 || dot(wi, h) >= 0.0
+        {
+            return None;
+        }
+        let denom = dot(wo, h) + eta * dot(wi, h);
+        Some((h, fresnel_dielectric(dot(wo, h), eta), Some(denom)))
+    }
+
+    fn eval_local(&self, wo: Vector3<f32>, wi: Vector3<f32>, eta: f32) -> f32
+    {
+        let ggx = &self.distribution;
+        match self.half_vector(wo, wi, eta)
+        {
+            None => 0.0,
+            Some((h, fresnel, None)) => fresnel * ggx.d(h) * ggx.g(wo, wi) / (4.0 * wo.z),
+            // Radiance is not rescaled by eta^2 on the way through, as in `Dielectric`
+            Some((h, fresnel, Some(denom))) =>
+                (1.0 - fresnel) * ggx.d(h) * ggx.g(wo, wi) * eta * eta * dot(wi, h).abs() * dot(wo, h) / (wo.z * denom * denom)
+        }
+    }
+
+    fn pdf_local(&self, wo: Vector3<f32>, wi: Vector3<f32>, eta: f32) -> f32
+    {
+        let ggx = &self.distribution;
+        match self.half_vector(wo, wi, eta)
+        {
+            None => 0.0,
+            Some((h, fresnel, None)) => fresnel * ggx.visible_pdf(wo, h) / (4.0 * dot(wo, h)),
+            Some((h, fresnel, Some(denom))) =>
+                (1.0 - fresnel) * ggx.visible_pdf(wo, h) * eta * eta * dot(wi, h).abs() / (denom * denom)
+        }
+    }
+}
+
+impl Material for RoughDielectric
+{
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, attenuation: &mut Vector3<f32>, scattered: &mut Ray) -> bool
+    {
+        let frame = ShadingFrame::new(rec.get_normal());
+        let wo = frame.to_local(-unit_vector3(r_in.direction()));
+        if wo.z <= 0.0
+        {
+            return false;
+        }
+
+        let eta = self.eta(r_in, rec);
+        let h = self.distribution.sample_visible(wo, random_double(), random_double());
+        let fresnel = fresnel_dielectric(dot(wo, h), eta);
+        let wi = if random_double() < fresnel
+        {
+            reflect_about(wo, h)
+        }
+        else
+        {
+            match refract_about(wo, h, eta)
+            {
+                Some(wi) => wi,
+                None => return false
+            }
+        };
+
+        let pdf = self.pdf_local(wo, wi, eta);
+        if pdf <= 0.0
+        {
+            return false;
+        }
+
+        scattered.clone_from(&Ray::with_time(rec.get_point(), frame.to_world(wi), r_in.time()));
+        attenuation.clone_from(&Vector3::from_value(self.eval_local(wo, wi, eta) / pdf));
+        true
+    }
+
+    fn scattering_pdf(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f32
+    {
+        let (wo, wi) = local_directions(r_in, rec, scattered);
+        self.pdf_local(wo, wi, self.eta(r_in, rec))
+    }
+
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Vector3<f32>
+    {
+        let (wo, wi) = local_directions(r_in, rec, scattered);
+        Vector3::from_value(self.eval_local(wo, wi, self.eta(r_in, rec)))
+    }
+
+    fn interior(&self, _wavelength: Option<f32>) -> Option<Interior>
+    {
+        Some(Interior { ior: self.ior, priority: self.priority, absorption: self.absorption })
+    }
+}
+
+#[cfg(test)]
+mod test
+{
+    use super::*;
+
+    #[test]
+    fn test_dielectric_absorption()
+    {
+        let glass = Dielectric::new(1.5).absorbing(Vector3::new(0.5, -1.0, 1.0));
+        assert_eq!(glass.interior(None).unwrap().absorption, Vector3::new(0.5, 0.0, 1.0));
+
+        // A tint is the colour reached after the given distance
+        let reached = (-absorption_from_tint(Vector3::new(0.2, 0.6, 1.0), 2.0) * 2.0).map(f32::exp);
+        assert!((reached - Vector3::new(0.2, 0.6, 1.0)).map(f32::abs).sum() < 1e-5);
+    }
+
+    #[test]
+    fn test_reflectance_is_reciprocal()
+    {
+        // Glass at normal incidence reflects 4% from either side
+        assert!((Dielectric::reflectance(1.0, 1.0, 1.5) - 0.04).abs() < 1e-6);
+        assert!((Dielectric::reflectance(1.0, 1.5, 1.0) - 0.04).abs() < 1e-6);
+
+        // Light leaving the glass is reflected as much as light refracted the other way
+        let cos_outside: f32 = 0.5;
+        let cos_inside = (1.0 - (1.0 - cos_outside * cos_outside) / (1.5 * 1.5)).sqrt();
+        assert!((Dielectric::reflectance(cos_inside, 1.5, 1.0) - Dielectric::reflectance(cos_outside, 1.0, 1.5)).abs() < 1e-6);
+        assert_eq!(Dielectric::reflectance(0.5, 1.5, 1.0), 1.0);
+
+        // Water in glass is a much weaker interface than water in air
+        assert!(Dielectric::reflectance(1.0, 1.33, 1.5) < 0.005);
+    }
 }
\ No newline at end of file
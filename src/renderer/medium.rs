@@ -0,0 +1,107 @@
+use std::sync::Arc;
+use cgmath::Vector3;
+use crate::renderer::aabb::Aabb;
+use crate::renderer::custom_function::{length, random_double};
+use crate::renderer::hittable::{HitRecord, Hittable};
+use crate::renderer::interval::Interval;
+use crate::renderer::material::Material;
+use crate::renderer::ray::Ray;
+use crate::utility::constants::INFINITY;
+
+// Volume of constant density filling a closed, convex `boundary` (fog, smoke).
+// A ray passing through scatters at an exponentially distributed distance; the
+// scattering direction comes from the `phase_function` material, e.g. Isotropic.
+pub struct ConstantMedium
+{
+    _boundary: Arc<dyn Hittable>,
+    _neg_inv_density: f32,
+    _phase_function: Arc<dyn Material>
+}
+
+impl ConstantMedium
+{
+    pub fn new(boundary: Arc<dyn Hittable>, density: f32, phase_function: Arc<dyn Material>) -> Self
+    {
+        ConstantMedium
+        {
+            _boundary: boundary,
+            _neg_inv_density: -1.0 / density,
+            _phase_function: phase_function
+        }
+    }
+}
+
+impl Hittable for ConstantMedium
+{
+    fn hit(&self, ray: Ray, ray_t: Interval) -> Option<HitRecord>
+    {
+        // Entry and exit of the boundary along the whole line, then clip to ray_t
+        let entry = self._boundary.hit(ray, Interval::universe())?;
+        let exit = self._boundary.hit(ray, Interval::new(entry.get_t() + 0.0001, INFINITY))?;
+
+        let t_enter = entry.get_t().max(ray_t.min()).max(0.0);
+        let t_exit = exit.get_t().min(ray_t.max());
+        if t_enter >= t_exit
+        {
+            return None;
+        }
+
+        let ray_length = length(ray.direction());
+        let distance_inside_boundary = (t_exit - t_enter) * ray_length;
+        let hit_distance = self._neg_inv_density * random_double().ln();
+        if hit_distance > distance_inside_boundary
+        {
+            return None;
+        }
+
+        let t = t_enter + hit_distance / ray_length;
+        // Normal and face are meaningless inside a volume, the phase function ignores them
+        Some(HitRecord{ _t: t, _point: ray.at(t), _normal: Vector3::new(1.0, 0.0, 0.0), _material: self._phase_function.clone(), _u: 0.0, _v: 0.0, _front_face: true })
+    }
+
+    fn bounding_box(&self) -> Aabb
+    {
+        self._boundary.bounding_box()
+    }
+}
+
+#[cfg(test)]
+mod test
+{
+    use super::*;
+    use cgmath::Array;
+    use crate::renderer::material::{Isotropic, Lambertian};
+    use crate::renderer::sampler::seed_thread_sampler;
+    use crate::renderer::sphere::Sphere;
+
+    #[test]
+    fn test_scatter_probability_follows_density()
+    {
+        let boundary = Arc::new(Sphere::new(Vector3::new(0.0, 0.0, 0.0), 1.0, Arc::new(Lambertian::new(Vector3::from_value(0.5)))));
+        let fog = ConstantMedium::new(boundary, 0.5, Arc::new(Isotropic::new(Vector3::from_value(1.0))));
+
+        // Through the centre the ray crosses 2 units of fog: P(scatter) = 1 - exp(-0.5 * 2)
+        let ray = Ray::new(Vector3::new(0.0, 0.0, 5.0), Vector3::new(0.0, 0.0, -2.0));
+        let trials = 20000;
+        let mut hits = 0;
+        for i in 0..trials
+        {
+            seed_thread_sampler(1, i, 0);
+            if let Some(rec) = fog.hit(ray, Interval::new(0.001, INFINITY))
+            {
+                assert!(rec.get_point().z <= 1.0 && rec.get_point().z >= -1.0);
+                hits += 1;
+            }
+        }
+        let expected = 1.0 - (-1.0f32).exp();
+        assert!((hits as f32 / trials as f32 - expected).abs() < 0.02);
+
+        // A ray starting inside only sees the fog ahead of it
+        let inside = Ray::new(Vector3::new(0.0, 0.0, 0.0), Vector3::new(0.0, 0.0, -1.0));
+        seed_thread_sampler(1, 0, 0);
+        if let Some(rec) = fog.hit(inside, Interval::new(0.001, INFINITY))
+        {
+            assert!(rec.get_t() > 0.0 && rec.get_t() <= 1.0);
+        }
+    }
+}
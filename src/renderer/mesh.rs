@@ -0,0 +1,185 @@
+use std::sync::Arc;
+use cgmath::Vector3;
+use crate::renderer::aabb::Aabb;
+use crate::renderer::bvh::BvhNode;
+use crate::renderer::custom_function::{length, random_double, unit_vector3};
+use crate::renderer::hittable::{area_pdf_value, HitRecord, Hittable};
+use crate::renderer::interval::Interval;
+use crate::renderer::material::Material;
+use crate::renderer::ray::Ray;
+use crate::renderer::triangle::{interpolate, intersect_triangle, random_on_triangle, triangle_hit_record};
+
+// Vertex and index buffers shared by every triangle of a mesh
+pub struct MeshData
+{
+    pub positions: Vec<Vector3<f32>>,
+    pub normals: Option<Vec<Vector3<f32>>>,
+    pub texcoords: Option<Vec<(f32, f32)>>,
+    pub indices: Vec<u32>
+}
+
+impl MeshData
+{
+    pub fn triangle_count(&self) -> usize
+    {
+        self.indices.len() / 3
+    }
+
+    fn vertex_indices(&self, triangle: usize) -> [usize; 3]
+    {
+        [self.indices[triangle * 3] as usize, self.indices[triangle * 3 + 1] as usize, self.indices[triangle * 3 + 2] as usize]
+    }
+
+    fn vertices(&self, triangle: usize) -> [Vector3<f32>; 3]
+    {
+        let [i0, i1, i2] = self.vertex_indices(triangle);
+        [self.positions[i0], self.positions[i1], self.positions[i2]]
+    }
+}
+
+// A single face of a mesh, only stores its index into the shared buffers
+struct MeshTriangle
+{
+    _data: Arc<MeshData>,
+    _index: usize,
+    _material: Arc<dyn Material>
+}
+
+impl Hittable for MeshTriangle
+{
+    fn hit(&self, ray: Ray, ray_t: Interval) -> Option<HitRecord>
+    {
+        let [i0, i1, i2] = self._data.vertex_indices(self._index);
+        let positions = &self._data.positions;
+        let (p0, p1, p2) = (positions[i0], positions[i1], positions[i2]);
+
+        let (t, b1, b2) = intersect_triangle(&ray, &ray_t, p0, p1, p2)?;
+
+        let geometric_normal = unit_vector3((p1 - p0).cross(p2 - p0));
+        let shading_normal = self._data.normals.as_ref().map(|n| unit_vector3(interpolate(b1, b2, n[i0], n[i1], n[i2])));
+        let uv = match &self._data.texcoords
+        {
+            Some(tc) =>
+            {
+                let b0 = 1.0 - b1 - b2;
+                (b0 * tc[i0].0 + b1 * tc[i1].0 + b2 * tc[i2].0, b0 * tc[i0].1 + b1 * tc[i1].1 + b2 * tc[i2].1)
+            }
+            None => (b1, b2)
+        };
+
+        Some(triangle_hit_record(ray, t, geometric_normal, shading_normal, uv, self._material.clone()))
+    }
+
+    fn bounding_box(&self) -> Aabb
+    {
+        let [i0, i1, i2] = self._data.vertex_indices(self._index);
+        let positions = &self._data.positions;
+        Aabb::surrounding(&Aabb::from_points(positions[i0], positions[i1]), &Aabb::from_points(positions[i2], positions[i2]))
+    }
+}
+
+pub struct Mesh
+{
+    _data: Arc<MeshData>,
+    _bvh: Option<BvhNode>,
+    _material: Arc<dyn Material>,
+    // Running total of the triangle areas, for picking one in proportion to its area
+    _cumulative_area: Vec<f32>
+}
+
+impl Mesh
+{
+    pub fn new(data: MeshData, material: Arc<dyn Material>) -> Self
+    {
+        assert_eq!(data.indices.len() % 3, 0, "mesh index count must be a multiple of three");
+        if let Some(normals) = &data.normals
+        {
+            assert_eq!(normals.len(), data.positions.len(), "mesh needs one normal per vertex");
+        }
+        if let Some(texcoords) = &data.texcoords
+        {
+            assert_eq!(texcoords.len(), data.positions.len(), "mesh needs one texture coordinate per vertex");
+        }
+
+        // Lights don't shade, and light sampling needs the geometric normal at every hit
+        let data = if material.is_emissive() { MeshData { normals: None, ..data } } else { data };
+
+        let mut total = 0.0;
+        let cumulative_area = (0..data.triangle_count()).map(|index|
+        {
+            let [p0, p1, p2] = data.vertices(index);
+            total += 0.5 * length((p1 - p0).cross(p2 - p0));
+            total
+        }).collect();
+
+        let data = Arc::new(data);
+        let mut triangles: Vec<Arc<dyn Hittable>> = (0..data.triangle_count())
+            .map(|index| Arc::new(MeshTriangle { _data: data.clone(), _index: index, _material: material.clone() }) as Arc<dyn Hittable>)
+            .collect();
+
+        let bvh = if triangles.is_empty() { None } else { Some(BvhNode::from_objects(&mut triangles)) };
+
+        Mesh
+        {
+            _data: data,
+            _bvh: bvh,
+            _material: material,
+            _cumulative_area: cumulative_area
+        }
+    }
+
+    pub fn data(&self) -> &MeshData
+    {
+        &self._data
+    }
+
+    pub fn material(&self) -> &Arc<dyn Material>
+    {
+        &self._material
+    }
+
+    fn area(&self) -> f32
+    {
+        self._cumulative_area.last().copied().unwrap_or(0.0)
+    }
+}
+
+impl Hittable for Mesh
+{
+    fn hit(&self, ray: Ray, ray_t: Interval) -> Option<HitRecord>
+    {
+        self._bvh.as_ref()?.hit(ray, ray_t)
+    }
+
+    fn bounding_box(&self) -> Aabb
+    {
+        match &self._bvh
+        {
+            Some(bvh) => bvh.bounding_box(),
+            None => Aabb::empty()
+        }
+    }
+
+    // Uniform over the surface: a triangle in proportion to its area, then a point on it
+    fn pdf_value(&self, origin: Vector3<f32>, direction: Vector3<f32>) -> f32
+    {
+        if self.area() <= 0.0
+        {
+            return 0.0;
+        }
+        area_pdf_value(self, self.area(), origin, direction)
+    }
+
+    fn random(&self, origin: Vector3<f32>) -> Vector3<f32>
+    {
+        if self._cumulative_area.is_empty()
+        {
+            return Vector3::new(1.0, 0.0, 0.0);
+        }
+
+        let target = random_double() * self.area();
+        let index = self._cumulative_area.partition_point(|&area| area <= target).min(self._cumulative_area.len() - 1);
+        let [p0, p1, p2] = self._data.vertices(index);
+        random_on_triangle(p0, p1, p2) - origin
+    }
+}
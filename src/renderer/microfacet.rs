@@ -0,0 +1,289 @@
+use cgmath::{dot, Vector3};
+use crate::renderer::custom_function::{orthonormal_basis, unit_vector3};
+use crate::utility::constants::PI;
+
+// Microfacet helpers for the rough materials. Everything here works in a local
+// shading frame with the normal along +z, see `ShadingFrame`.
+
+// Tangent frame around a hit normal. The tangents follow `orthonormal_basis`,
+// which is what anisotropic roughness is aligned with.
+#[derive(Copy, Clone, Debug)]
+pub struct ShadingFrame
+{
+    _tangent: Vector3<f32>,
+    _bitangent: Vector3<f32>,
+    _normal: Vector3<f32>
+}
+
+impl ShadingFrame
+{
+    pub fn new(normal: Vector3<f32>) -> Self
+    {
+        let normal = unit_vector3(normal);
+        let (tangent, bitangent) = orthonormal_basis(normal);
+        ShadingFrame { _tangent: tangent, _bitangent: bitangent, _normal: normal }
+    }
+
+    pub fn to_local(self, v: Vector3<f32>) -> Vector3<f32>
+    {
+        Vector3::new(dot(v, self._tangent), dot(v, self._bitangent), dot(v, self._normal))
+    }
+
+    pub fn to_world(self, v: Vector3<f32>) -> Vector3<f32>
+    {
+        v.x * self._tangent + v.y * self._bitangent + v.z * self._normal
+    }
+}
+
+// Trowbridge-Reitz (GGX) distribution of microfacet normals, with separate
+// widths along the two tangents
+#[derive(Copy, Clone, Debug)]
+pub struct Ggx
+{
+    _alpha_x: f32,
+    _alpha_y: f32
+}
+
+impl Ggx
+{
+    // `roughness` in [0, 1] is squared into the GGX width. `anisotropy` in [-1, 1]
+    // stretches the highlight along the first tangent for positive values, the
+    // second for negative ones.
+    pub fn new(roughness: f32, anisotropy: f32) -> Self
+    {
+        let alpha = roughness.clamp(0.0, 1.0).powi(2);
+        let aspect = (1.0 - 0.9 * anisotropy.abs().min(1.0)).sqrt();
+        let (wide, narrow) = ((alpha / aspect).max(1e-3), (alpha * aspect).max(1e-3));
+        let (alpha_x, alpha_y) = if anisotropy >= 0.0 { (wide, narrow) } else { (narrow, wide) };
+        Ggx { _alpha_x: alpha_x, _alpha_y: alpha_y }
+    }
+
+    // Density of microfacet normal `h` per unit projected area
+    pub fn d(&self, h: Vector3<f32>) -> f32
+    {
+        if h.z <= 0.0
+        {
+            return 0.0;
+        }
+        let (x, y) = (h.x / self._alpha_x, h.y / self._alpha_y);
+        let e = x * x + y * y + h.z * h.z;
+        1.0 / (PI * self._alpha_x * self._alpha_y * e * e)
+    }
+
+    fn lambda(&self, w: Vector3<f32>) -> f32
+    {
+        if w.z == 0.0
+        {
+            return f32::INFINITY;
+        }
+        let (x, y) = (self._alpha_x * w.x, self._alpha_y * w.y);
+        ((1.0 + (x * x + y * y) / (w.z * w.z)).sqrt() - 1.0) * 0.5
+    }
+
+    // Fraction of microfacets visible from `w`
+    pub fn g1(&self, w: Vector3<f32>) -> f32
+    {
+        1.0 / (1.0 + self.lambda(w))
+    }
+
+    // Height-correlated masking and shadowing of the pair
+    pub fn g(&self, wo: Vector3<f32>, wi: Vector3<f32>) -> f32
+    {
+        1.0 / (1.0 + self.lambda(wo) + self.lambda(wi))
+    }
+
+    // Density of `sample_visible` returning `h` when seen from `wo`
+    pub fn visible_pdf(&self, wo: Vector3<f32>, h: Vector3<f32>) -> f32
+    {
+        if wo.z <= 0.0
+        {
+            return 0.0;
+        }
+        self.g1(wo) * dot(wo, h).max(0.0) * self.d(h) / wo.z
+    }
+
+    // Microfacet normal as seen from `wo` (upper hemisphere), Heitz 2018
+    pub fn sample_visible(&self, wo: Vector3<f32>, u1: f32, u2: f32) -> Vector3<f32>
+    {
+        // Stretch the view into the configuration of a hemisphere of unit roughness
+        let vh = unit_vector3(Vector3::new(self._alpha_x * wo.x, self._alpha_y * wo.y, wo.z));
+
+        let lensq = vh.x * vh.x + vh.y * vh.y;
+        let t1 = if lensq > 0.0 { Vector3::new(-vh.y, vh.x, 0.0) / lensq.sqrt() } else { Vector3::new(1.0, 0.0, 0.0) };
+        let t2 = vh.cross(t1);
+
+        // Point on the projected half disk
+        let r = u1.sqrt();
+        let phi = 2.0 * PI * u2;
+        let p1 = r * phi.cos();
+        let s = 0.5 * (1.0 + vh.z);
+        let p2 = (1.0 - s) * (1.0 - p1 * p1).max(0.0).sqrt() + s * r * phi.sin();
+
+        let nh = p1 * t1 + p2 * t2 + (1.0 - p1 * p1 - p2 * p2).max(0.0).sqrt() * vh;
+        unit_vector3(Vector3::new(self._alpha_x * nh.x, self._alpha_y * nh.y, nh.z.max(1e-6)))
+    }
+}
+
+// Unpolarised reflectance of a dielectric interface, `eta` is the ratio of the
+// far side's index to the near side's. 1 under total internal reflection.
+pub fn fresnel_dielectric(cos_i: f32, eta: f32) -> f32
+{
+    let cos_i = cos_i.clamp(0.0, 1.0);
+    let sin2_t = (1.0 - cos_i * cos_i) / (eta * eta);
+    if sin2_t >= 1.0
+    {
+        return 1.0;
+    }
+    let cos_t = (1.0 - sin2_t).sqrt();
+
+    let rs = (cos_i - eta * cos_t) / (cos_i + eta * cos_t);
+    let rp = (eta * cos_i - cos_t) / (eta * cos_i + cos_t);
+    0.5 * (rs * rs + rp * rp)
+}
+
+// Unpolarised reflectance of a metal with complex index `eta + i k`, per channel
+pub fn fresnel_conductor(cos_i: f32, eta: Vector3<f32>, k: Vector3<f32>) -> Vector3<f32>
+{
+    let channel = |eta: f32, k: f32|
+    {
+        let cos2 = cos_i.clamp(0.0, 1.0).powi(2);
+        let sin2 = 1.0 - cos2;
+
+        let t0 = eta * eta - k * k - sin2;
+        let a2b2 = (t0 * t0 + 4.0 * eta * eta * k * k).sqrt();
+        let t1 = a2b2 + cos2;
+        let a = (0.5 * (a2b2 + t0)).max(0.0).sqrt();
+        let t2 = 2.0 * cos2.sqrt() * a;
+        let rs = (t1 - t2) / (t1 + t2);
+
+        let t3 = cos2 * a2b2 + sin2 * sin2;
+        let t4 = t2 * sin2;
+        let rp = rs * (t3 - t4) / (t3 + t4);
+
+        0.5 * (rp + rs)
+    };
+    Vector3::new(channel(eta.x, k.x), channel(eta.y, k.y), channel(eta.z, k.z))
+}
+
+// Relative index kept slightly away from 1. Where both sides match the
+// refraction half vector degenerates to zero, while a barely mismatched
+// interface transmits practically straight through just like a matched one.
+pub fn distinct_eta(eta: f32) -> f32
+{
+    if (eta - 1.0).abs() < 1e-3 { if eta < 1.0 { 1.0 - 1e-3 } else { 1.0 + 1e-3 } } else { eta }
+}
+
+// Mirror of `wo` about the microfacet normal `h`
+pub fn reflect_about(wo: Vector3<f32>, h: Vector3<f32>) -> Vector3<f32>
+{
+    2.0 * dot(wo, h) * h - wo
+}
+
+// Refraction of `wo` through the microfacet normal `h` (on the side of `wo`)
+// into a medium `eta` times denser, `None` under total internal reflection
+pub fn refract_about(wo: Vector3<f32>, h: Vector3<f32>, eta: f32) -> Option<Vector3<f32>>
+{
+    let cos_i = dot(wo, h);
+    let sin2_t = (1.0 - cos_i * cos_i).max(0.0) / (eta * eta);
+    if sin2_t >= 1.0
+    {
+        return None;
+    }
+    let cos_t = (1.0 - sin2_t).sqrt();
+    Some(-wo / eta + (cos_i / eta - cos_t) * h)
+}
+
+#[cfg(test)]
+mod test
+{
+    use std::sync::Arc;
+    use cgmath::{Array, InnerSpace};
+    use super::*;
+    use crate::renderer::custom_function::random_unit_vector3;
+    use crate::renderer::hittable::HitRecord;
+    use crate::renderer::material::{Conductor, Material, RoughDielectric};
+    use crate::renderer::ray::Ray;
+    use crate::renderer::sampler::seed_thread_sampler;
+
+    #[test]
+    fn test_fresnel()
+    {
+        // Glass at normal incidence reflects ((1.5 - 1) / (1.5 + 1))^2 = 4%
+        assert!((fresnel_dielectric(1.0, 1.5) - 0.04).abs() < 1e-6);
+        assert_eq!(fresnel_dielectric(0.1, 1.0 / 1.5), 1.0);
+        assert!((fresnel_dielectric(0.0, 1.5) - 1.0).abs() < 1e-6);
+
+        // With k = 0 the conductor formula reduces to the dielectric one
+        let f = fresnel_conductor(0.6, Vector3::from_value(1.5), Vector3::from_value(0.0));
+        assert!((f.x - fresnel_dielectric(0.6, 1.5)).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_visible_normals_are_normalised()
+    {
+        // The visible normal density integrates to one over the hemisphere for any view
+        let ggx = Ggx::new(0.6, 0.5);
+        let wo = Vector3::new(0.5, -0.3, 0.6).normalize();
+        let n = 400;
+        let mut sum = 0.0;
+        for i in 0..n
+        {
+            for j in 0..n
+            {
+                // Midpoint rule over (cos theta, phi)
+                let cos_theta = (i as f32 + 0.5) / n as f32;
+                let phi = 2.0 * PI * (j as f32 + 0.5) / n as f32;
+                let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+                let h = Vector3::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta);
+                sum += ggx.visible_pdf(wo, h) * 2.0 * PI / (n * n) as f32;
+            }
+        }
+        assert!((sum - 1.0).abs() < 0.01, "{}", sum);
+
+        let h = ggx.sample_visible(wo, 0.3, 0.7);
+        assert!((h.magnitude() - 1.0).abs() < 1e-5 && h.z > 0.0 && dot(h, wo) > 0.0);
+    }
+
+    // The mean attenuation of `scatter` is the integral of `eval` over all
+    // directions, which uniform sampling estimates independently
+    fn check_sampling(material: Arc<dyn Material>, front_face: bool)
+    {
+        seed_thread_sampler(5, 0, 0);
+        let rec = HitRecord{ _t: 1.0, _point: Vector3::from_value(0.0), _normal: Vector3::new(0.0, 0.0, 1.0), _material: material.clone(), _u: 0.0, _v: 0.0, _front_face: front_face };
+        let r_in = Ray::new(Vector3::new(-0.6, 0.0, 0.8), Vector3::new(0.6, 0.0, -0.8));
+
+        let n = 200_000;
+        let mut sampled = 0.0;
+        let mut attenuation = Vector3::from_value(0.0);
+        let mut scattered = r_in;
+        for _ in 0..n
+        {
+            if material.scatter(&r_in, &rec, &mut attenuation, &mut scattered)
+            {
+                sampled += attenuation.x;
+                let expected = material.eval(&r_in, &rec, &scattered).x / material.scattering_pdf(&r_in, &rec, &scattered);
+                assert!((attenuation.x - expected).abs() <= 1e-3 * expected);
+            }
+        }
+
+        let mut uniform = 0.0;
+        for _ in 0..n
+        {
+            let direction = Ray::new(Vector3::from_value(0.0), random_unit_vector3());
+            uniform += material.eval(&r_in, &rec, &direction).x * 4.0 * PI;
+        }
+
+        let (sampled, uniform) = (sampled / n as f32, uniform / n as f32);
+        assert!((sampled - uniform).abs() < 0.02, "{} vs {}", sampled, uniform);
+        assert!(sampled <= 1.0 && sampled > 0.5);
+    }
+
+    #[test]
+    fn test_materials_sample_their_own_density()
+    {
+        check_sampling(Arc::new(Conductor::preset("gold", 0.5, 0.0).unwrap()), true);
+        check_sampling(Arc::new(Conductor::preset("aluminium", 0.6, 0.8).unwrap()), true);
+        check_sampling(Arc::new(RoughDielectric::new(1.5, 0.5, 0.0)), true);
+        check_sampling(Arc::new(RoughDielectric::new(1.5, 0.4, 0.0)), false);
+    }
+}
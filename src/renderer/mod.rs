@@ -7,4 +7,29 @@ mod hittable;
 mod sphere;
 mod hittable_list;
 pub mod interval;
-mod material;
\ No newline at end of file
+mod material;
+pub mod film;
+mod aabb;
+mod bvh;
+pub mod scene;
+pub mod scene_file;
+mod triangle;
+pub mod mesh;
+pub mod obj_loader;
+pub mod background;
+mod texture;
+mod perlin;
+mod sampler;
+pub mod tile_scheduler;
+pub mod settings;
+mod animated;
+pub mod transform;
+mod quad;
+mod plane;
+mod cylinder;
+mod medium;
+pub mod pdf;
+mod microfacet;
+mod principled;
+mod spectrum;
+mod ior_stack;
\ No newline at end of file
@@ -0,0 +1,285 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use std::sync::Arc;
+use cgmath::Vector3;
+use crate::renderer::material::{Dielectric, DiffuseLight, Lambertian, Material, Metal};
+use crate::renderer::mesh::{Mesh, MeshData};
+use crate::renderer::principled::Principled;
+
+// Loads every model of an OBJ file as its own `Mesh`. Materials come from the
+// referenced MTL file unless `material_override` is given, in which case the
+// MTL file is not read at all and may be missing. Texture maps such as map_Kd
+// are not supported, materials only take their constant colours.
+pub fn load_obj(path: &Path, material_override: Option<Arc<dyn Material>>) -> Result<Vec<Mesh>, String>
+{
+    let file = File::open(path).map_err(|err| format!("failed to open {}: {}", path.display(), err))?;
+    let dir = path.parent().unwrap_or_else(|| Path::new(""));
+    let (models, materials) = read_obj(&mut BufReader::new(file), dir, material_override.is_none())
+        .map_err(|err| format!("failed to load {}: {}", path.display(), err))?;
+
+    let converted: Vec<Arc<dyn Material>> = materials.iter().map(convert_material).collect();
+    let use_mtl = material_override.is_none();
+    let fallback: Arc<dyn Material> = match material_override
+    {
+        Some(material) => material,
+        None => Arc::new(Lambertian::new(Vector3::new(0.8, 0.8, 0.8)))
+    };
+
+    let mut meshes = vec![];
+    for model in models.iter()
+    {
+        let data = mesh_data(&model.mesh)?;
+        if data.triangle_count() == 0
+        {
+            continue;
+        }
+
+        let material = match model.mesh.material_id
+        {
+            Some(id) if use_mtl && id < converted.len() => converted[id].clone(),
+            _ => fallback.clone()
+        };
+        meshes.push(Mesh::new(data, material));
+    }
+
+    Ok(meshes)
+}
+
+// Parses OBJ text, reading MTL files relative to `dir` only if `with_materials`.
+// A broken MTL file is reported by name rather than as a failure of the OBJ.
+fn read_obj<B: BufRead>(reader: &mut B, dir: &Path, with_materials: bool) -> Result<(Vec<tobj::Model>, Vec<tobj::Material>), String>
+{
+    let mtl_error = RefCell::new(None);
+    let loaded = tobj::load_obj_buf(reader, |mtl_path|
+    {
+        if !with_materials
+        {
+            return Ok((Vec::new(), HashMap::new()));
+        }
+        let full_path = dir.join(mtl_path);
+        tobj::load_mtl(&full_path).map_err(|err|
+        {
+            *mtl_error.borrow_mut() = Some(format!("material library {}: {}", full_path.display(), err));
+            err
+        })
+    });
+    loaded.map_err(|err| mtl_error.borrow_mut().take().unwrap_or_else(|| err.to_string()))
+}
+
+fn mesh_data(mesh: &tobj::Mesh) -> Result<MeshData, String>
+{
+    let vertex_count = mesh.positions.len() / 3;
+
+    let positions: Vec<Vector3<f32>> = mesh.positions.chunks(3)
+        .map(|p| Vector3::new(p[0], p[1], p[2]))
+        .collect();
+
+    // Normals and texture coordinates are optional in OBJ, only keep complete sets
+    let normals = if mesh.normals.len() == vertex_count * 3 && vertex_count > 0
+    {
+        Some(mesh.normals.chunks(3).map(|n| Vector3::new(n[0], n[1], n[2])).collect())
+    }
+    else
+    {
+        None
+    };
+
+    let texcoords = if mesh.texcoords.len() == vertex_count * 2 && vertex_count > 0
+    {
+        Some(mesh.texcoords.chunks(2).map(|t| (t[0], t[1])).collect())
+    }
+    else
+    {
+        None
+    };
+
+    if !mesh.indices.len().is_multiple_of(3)
+    {
+        return Err(format!("mesh has {} indices, expected triangles", mesh.indices.len()));
+    }
+    if let Some(bad) = mesh.indices.iter().find(|&&i| i as usize >= vertex_count)
+    {
+        return Err(format!("mesh index {} is out of range for {} vertices", bad, vertex_count));
+    }
+
+    Ok(MeshData
+    {
+        positions,
+        normals,
+        texcoords,
+        indices: mesh.indices.clone()
+    })
+}
+
+fn max_component(c: [f32; 3]) -> f32
+{
+    c[0].max(c[1]).max(c[2])
+}
+
+// MTL emission, tobj keeps the non standard `Ke` statement as an unknown parameter
+fn emission(mtl: &tobj::Material) -> Option<[f32; 3]>
+{
+    let values: Vec<f32> = mtl.unknown_param.get("Ke")?
+        .split_whitespace()
+        .filter_map(|v| v.parse::<f32>().ok())
+        .collect();
+
+    match values.len()
+    {
+        1 => Some([values[0]; 3]),
+        3 => Some([values[0], values[1], values[2]]),
+        _ => None
+    }
+}
+
+// Single value of a non standard MTL statement such as the PBR extension's `Pr`
+fn scalar_param(mtl: &tobj::Material, key: &str) -> Option<f32>
+{
+    mtl.unknown_param.get(key)?.split_whitespace().next()?.parse::<f32>().ok()
+}
+
+// Principled material from the PBR extension written by most DCC exporters
+// (Pr, Pm, Ps, Pc, Pcr, aniso), `None` if the MTL uses none of them
+fn principled(mtl: &tobj::Material) -> Option<Principled>
+{
+    let keys = ["Pr", "Pm", "Ps", "Pc", "Pcr", "aniso"];
+    if !keys.iter().any(|key| scalar_param(mtl, key).is_some())
+    {
+        return None;
+    }
+
+    let param = |key: &str, default: f32| scalar_param(mtl, key).unwrap_or(default);
+    Some(Principled::new(Vector3::new(mtl.diffuse[0], mtl.diffuse[1], mtl.diffuse[2]))
+        .roughness(param("Pr", 0.5))
+        .metallic(param("Pm", 0.0))
+        .sheen(param("Ps", 0.0))
+        .clearcoat(param("Pc", 0.0))
+        .clearcoat_gloss(1.0 - param("Pcr", 0.0))
+        .anisotropy(param("aniso", 0.0))
+        .transmission(1.0 - mtl.dissolve.clamp(0.0, 1.0)))
+}
+
+// Maps MTL parameters onto the closest CPU material:
+//   emissive    (Ke > 0)                -> DiffuseLight
+//   PBR extension (Pr, Pm, ...)         -> Principled, transmission from d
+//   transparent (d < 1 or illum 4/6/7)  -> Dielectric with Ni as ior
+//   reflective  (illum 3/5)             -> Metal tinted by Ks, roughness from Ns
+//   everything else                     -> Lambertian with Kd
+fn convert_material(mtl: &tobj::Material) -> Arc<dyn Material>
+{
+    let illum = mtl.illumination_model.unwrap_or(2);
+
+    if let Some(ke) = emission(mtl)
+    {
+        if max_component(ke) > 0.0
+        {
+            return Arc::new(DiffuseLight::new(Vector3::new(ke[0], ke[1], ke[2])));
+        }
+    }
+
+    if let Some(material) = principled(mtl)
+    {
+        return Arc::new(material);
+    }
+
+    if mtl.dissolve < 1.0 || illum == 4 || illum == 6 || illum == 7
+    {
+        let ior = if mtl.optical_density > 0.0 { mtl.optical_density } else { 1.5 };
+        return Arc::new(Dielectric::new(ior));
+    }
+
+    if illum == 3 || illum == 5
+    {
+        let tint = if max_component(mtl.specular) > 0.0 { mtl.specular } else { mtl.diffuse };
+        // Blinn-Phong exponent to a roughness like fuzz value
+        let fuzz = (2.0 / (mtl.shininess.max(0.0) + 2.0)).sqrt();
+        return Arc::new(Metal::new(Vector3::new(tint[0], tint[1], tint[2]), fuzz));
+    }
+
+    Arc::new(Lambertian::new(Vector3::new(mtl.diffuse[0], mtl.diffuse[1], mtl.diffuse[2])))
+}
+
+#[cfg(test)]
+mod test
+{
+    use super::*;
+
+    #[test]
+    fn test_mesh_data_without_normals_or_texcoords()
+    {
+        let mesh = tobj::Mesh
+        {
+            positions: vec![0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 1.0, 1.0, 0.0],
+            normals: vec![],
+            texcoords: vec![],
+            indices: vec![0, 1, 2, 2, 1, 3],
+            material_id: None
+        };
+
+        let data = mesh_data(&mesh).unwrap();
+        assert_eq!(data.positions.len(), 4);
+        assert_eq!(data.triangle_count(), 2);
+        assert!(data.normals.is_none());
+        assert!(data.texcoords.is_none());
+    }
+
+    #[test]
+    fn test_mesh_data_rejects_bad_indices()
+    {
+        let mesh = tobj::Mesh
+        {
+            positions: vec![0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0],
+            normals: vec![],
+            texcoords: vec![],
+            indices: vec![0, 1, 3],
+            material_id: None
+        };
+
+        assert!(mesh_data(&mesh).is_err());
+    }
+
+    #[test]
+    fn test_missing_mtl_only_matters_without_override()
+    {
+        let obj = "mtllib missing.mtl\nv 0 0 0\nv 1 0 0\nv 0 1 0\nusemtl red\nf 1 2 3\n";
+
+        let (models, materials) = read_obj(&mut obj.as_bytes(), Path::new("no_such_dir"), false).unwrap();
+        assert_eq!(models.len(), 1);
+        assert!(materials.is_empty());
+        assert_eq!(models[0].mesh.material_id, None);
+
+        let err = read_obj(&mut obj.as_bytes(), Path::new("no_such_dir"), true).err().unwrap();
+        assert!(err.contains("missing.mtl"), "{}", err);
+    }
+
+    #[test]
+    fn test_pbr_extension_selects_principled()
+    {
+        let mut mtl = tobj::Material
+        {
+            name: "plastic".to_string(),
+            ambient: [0.0; 3],
+            diffuse: [0.8, 0.1, 0.1],
+            specular: [0.0; 3],
+            shininess: 0.0,
+            dissolve: 1.0,
+            optical_density: 1.5,
+            ambient_texture: String::new(),
+            diffuse_texture: String::new(),
+            specular_texture: String::new(),
+            normal_texture: String::new(),
+            dissolve_texture: String::new(),
+            illumination_model: None,
+            unknown_param: Default::default()
+        };
+        assert!(principled(&mtl).is_none());
+
+        mtl.unknown_param.insert("Pr".to_string(), "0.3".to_string());
+        assert!(principled(&mtl).is_some());
+        assert_eq!(scalar_param(&mtl, "Pr"), Some(0.3));
+        assert_eq!(scalar_param(&mtl, "Pm"), None);
+    }
+}
@@ -0,0 +1,204 @@
+use cgmath::{dot, Vector3};
+use crate::renderer::custom_function::{orthonormal_basis, random_cosine_direction, random_double, random_unit_vector3, unit_vector3};
+use crate::renderer::hittable::Hittable;
+use crate::utility::constants::PI;
+
+// A distribution of directions, per unit solid angle. `generate` draws from it
+// and `value` gives its density in any direction, which need not be normalised.
+pub trait Pdf
+{
+    fn value(&self, direction: Vector3<f32>) -> f32;
+
+    fn generate(&self) -> Vector3<f32>;
+}
+
+// Every direction equally likely
+pub struct SpherePdf;
+
+impl Pdf for SpherePdf
+{
+    fn value(&self, _direction: Vector3<f32>) -> f32
+    {
+        1.0 / (4.0 * PI)
+    }
+
+    fn generate(&self) -> Vector3<f32>
+    {
+        random_unit_vector3()
+    }
+}
+
+// cos(theta) / pi over the hemisphere around `normal`, matches a Lambertian surface exactly
+pub struct CosinePdf
+{
+    _normal: Vector3<f32>,
+    _tangent: Vector3<f32>,
+    _bitangent: Vector3<f32>
+}
+
+impl CosinePdf
+{
+    pub fn new(normal: Vector3<f32>) -> Self
+    {
+        let normal = unit_vector3(normal);
+        let (tangent, bitangent) = orthonormal_basis(normal);
+        CosinePdf
+        {
+            _normal: normal,
+            _tangent: tangent,
+            _bitangent: bitangent
+        }
+    }
+}
+
+impl Pdf for CosinePdf
+{
+    fn value(&self, direction: Vector3<f32>) -> f32
+    {
+        let cosine = dot(unit_vector3(direction), self._normal);
+        cosine.max(0.0) / PI
+    }
+
+    fn generate(&self) -> Vector3<f32>
+    {
+        let d = random_cosine_direction();
+        d.x * self._tangent + d.y * self._bitangent + d.z * self._normal
+    }
+}
+
+// Directions from `origin` towards points on `objects`, usually the scene's lights
+pub struct HittablePdf<'a>
+{
+    _objects: &'a dyn Hittable,
+    _origin: Vector3<f32>
+}
+
+impl<'a> HittablePdf<'a>
+{
+    pub fn new(objects: &'a dyn Hittable, origin: Vector3<f32>) -> Self
+    {
+        HittablePdf
+        {
+            _objects: objects,
+            _origin: origin
+        }
+    }
+}
+
+impl<'a> Pdf for HittablePdf<'a>
+{
+    fn value(&self, direction: Vector3<f32>) -> f32
+    {
+        self._objects.pdf_value(self._origin, direction)
+    }
+
+    fn generate(&self) -> Vector3<f32>
+    {
+        self._objects.random(self._origin)
+    }
+}
+
+// Draws from either of two distributions with equal probability
+pub struct MixturePdf<'a>
+{
+    _p: [&'a dyn Pdf; 2]
+}
+
+impl<'a> MixturePdf<'a>
+{
+    pub fn new(p0: &'a dyn Pdf, p1: &'a dyn Pdf) -> Self
+    {
+        MixturePdf
+        {
+            _p: [p0, p1]
+        }
+    }
+}
+
+impl<'a> Pdf for MixturePdf<'a>
+{
+    fn value(&self, direction: Vector3<f32>) -> f32
+    {
+        0.5 * self._p[0].value(direction) + 0.5 * self._p[1].value(direction)
+    }
+
+    fn generate(&self) -> Vector3<f32>
+    {
+        if random_double() < 0.5
+        {
+            self._p[0].generate()
+        }
+        else
+        {
+            self._p[1].generate()
+        }
+    }
+}
+
+// Weight of a sample drawn with density `pdf` when `other_pdf` could have found it
+// as well (Veach's power heuristic, beta = 2). The two weights of a direction sum to one.
+pub fn power_heuristic(pdf: f32, other_pdf: f32) -> f32
+{
+    let (a, b) = (pdf * pdf, other_pdf * other_pdf);
+    if a + b > 0.0 { a / (a + b) } else { 0.0 }
+}
+
+#[cfg(test)]
+mod test
+{
+    use std::sync::Arc;
+    use cgmath::{Array, InnerSpace};
+    use super::*;
+    use crate::renderer::hittable_list::HittableList;
+    use crate::renderer::material::DiffuseLight;
+    use crate::renderer::quad::Quad;
+    use crate::renderer::sampler::seed_thread_sampler;
+    use crate::renderer::sphere::Sphere;
+
+    // Monte Carlo estimate of the integral of `pdf` over the sphere, one for a normalised density
+    fn integrate(pdf: &dyn Pdf) -> f32
+    {
+        let n = 200_000;
+        let uniform = SpherePdf;
+        (0..n).map(|_| { let d = uniform.generate(); pdf.value(d) / uniform.value(d) }).sum::<f32>() / n as f32
+    }
+
+    #[test]
+    fn test_densities_integrate_to_one()
+    {
+        seed_thread_sampler(7, 0, 0);
+        let light = Arc::new(DiffuseLight::new(Vector3::from_value(1.0)));
+        let mut lights = HittableList::new();
+        lights.add(Arc::new(Quad::new(Vector3::new(-1.0, 2.0, -1.0), Vector3::new(2.0, 0.0, 0.0), Vector3::new(0.0, 0.0, 2.0), light.clone())));
+        lights.add(Arc::new(Sphere::new(Vector3::new(3.0, 0.0, 0.0), 1.0, light)));
+
+        let cosine = CosinePdf::new(Vector3::new(0.0, 1.0, 0.0));
+        let towards_lights = HittablePdf::new(&lights, Vector3::from_value(0.0));
+        let mixture = MixturePdf::new(&cosine, &towards_lights);
+        for pdf in &[&cosine as &dyn Pdf, &towards_lights as &dyn Pdf, &mixture as &dyn Pdf]
+        {
+            assert!((integrate(*pdf) - 1.0).abs() < 0.03);
+        }
+    }
+
+    #[test]
+    fn test_generated_directions_have_density()
+    {
+        seed_thread_sampler(7, 0, 1);
+        let light = Arc::new(DiffuseLight::new(Vector3::from_value(1.0)));
+        let sphere = Sphere::new(Vector3::new(0.0, 0.0, -4.0), 1.0, light);
+        let towards_sphere = HittablePdf::new(&sphere, Vector3::from_value(0.0));
+
+        // Uniform over the cone subtended by the sphere: 1 / (2 pi (1 - cos(theta_max)))
+        let expected = 1.0 / (2.0 * PI * (1.0 - (15.0f32 / 16.0).sqrt()));
+        for _ in 0..100
+        {
+            let d = towards_sphere.generate();
+            assert!((towards_sphere.value(d) - expected).abs() / expected < 1e-3);
+            assert!(d.normalize().z < 0.0);
+        }
+        assert_eq!(towards_sphere.value(Vector3::new(0.0, 0.0, 1.0)), 0.0);
+
+        assert!((power_heuristic(1.0, 3.0) + power_heuristic(3.0, 1.0) - 1.0).abs() < 1e-6);
+    }
+}
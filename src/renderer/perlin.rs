@@ -0,0 +1,112 @@
+use cgmath::{dot, Vector3};
+use crate::renderer::custom_function::{random_int, random_vector3_range, unit_vector3};
+
+const POINT_COUNT: usize = 256;
+
+// Gradient noise over a lattice of random unit vectors
+pub struct Perlin
+{
+    randvec: Vec<Vector3<f32>>,
+    perm_x: Vec<usize>,
+    perm_y: Vec<usize>,
+    perm_z: Vec<usize>
+}
+
+impl Perlin
+{
+    pub fn new() -> Self
+    {
+        let randvec = (0..POINT_COUNT).map(|_| unit_vector3(random_vector3_range(-1.0, 1.0))).collect();
+
+        Perlin
+        {
+            randvec,
+            perm_x: Perlin::generate_perm(),
+            perm_y: Perlin::generate_perm(),
+            perm_z: Perlin::generate_perm()
+        }
+    }
+
+    // Smooth noise in roughly [-1, 1]
+    pub fn noise(&self, p: Vector3<f32>) -> f32
+    {
+        let u = p.x - p.x.floor();
+        let v = p.y - p.y.floor();
+        let w = p.z - p.z.floor();
+
+        let i = p.x.floor() as i64;
+        let j = p.y.floor() as i64;
+        let k = p.z.floor() as i64;
+
+        let mut c = [[[Vector3::new(0.0, 0.0, 0.0); 2]; 2]; 2];
+        for (di, plane) in c.iter_mut().enumerate()
+        {
+            for (dj, row) in plane.iter_mut().enumerate()
+            {
+                for (dk, corner) in row.iter_mut().enumerate()
+                {
+                    *corner = self.randvec[
+                        self.perm_x[((i + di as i64) & 255) as usize] ^
+                        self.perm_y[((j + dj as i64) & 255) as usize] ^
+                        self.perm_z[((k + dk as i64) & 255) as usize]];
+                }
+            }
+        }
+
+        Perlin::perlin_interp(&c, u, v, w)
+    }
+
+    // Sum of `depth` octaves of noise with halving weights
+    pub fn turb(&self, p: Vector3<f32>, depth: u32) -> f32
+    {
+        let mut accum = 0.0;
+        let mut temp_p = p;
+        let mut weight = 1.0;
+
+        for _ in 0..depth
+        {
+            accum += weight * self.noise(temp_p);
+            weight *= 0.5;
+            temp_p *= 2.0;
+        }
+
+        accum.abs()
+    }
+
+    fn generate_perm() -> Vec<usize>
+    {
+        let mut p: Vec<usize> = (0..POINT_COUNT).collect();
+        for i in (1..POINT_COUNT).rev()
+        {
+            p.swap(i, random_int(0, i));
+        }
+        p
+    }
+
+    fn perlin_interp(c: &[[[Vector3<f32>; 2]; 2]; 2], u: f32, v: f32, w: f32) -> f32
+    {
+        // Hermite smoothing hides the lattice
+        let uu = u * u * (3.0 - 2.0 * u);
+        let vv = v * v * (3.0 - 2.0 * v);
+        let ww = w * w * (3.0 - 2.0 * w);
+
+        let mut accum = 0.0;
+        for (i, plane) in c.iter().enumerate()
+        {
+            for (j, row) in plane.iter().enumerate()
+            {
+                for (k, corner) in row.iter().enumerate()
+                {
+                    let (fi, fj, fk) = (i as f32, j as f32, k as f32);
+                    let weight_v = Vector3::new(u - fi, v - fj, w - fk);
+                    accum += (fi * uu + (1.0 - fi) * (1.0 - uu))
+                           * (fj * vv + (1.0 - fj) * (1.0 - vv))
+                           * (fk * ww + (1.0 - fk) * (1.0 - ww))
+                           * dot(*corner, weight_v);
+                }
+            }
+        }
+
+        accum
+    }
+}
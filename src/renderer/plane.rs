@@ -0,0 +1,183 @@
+use std::sync::Arc;
+use cgmath::{dot, Vector3};
+use crate::renderer::aabb::Aabb;
+use crate::renderer::custom_function::{length, length_squared, orthonormal_basis, random_in_unit_disk, set_face_normal, unit_vector3};
+use crate::renderer::hittable::{HitRecord, Hittable};
+use crate::renderer::interval::Interval;
+use crate::renderer::material::Material;
+use crate::renderer::ray::Ray;
+use crate::utility::constants::PI;
+
+// Distance along the ray to the plane through `point` with unit `normal`
+fn plane_hit(ray: &Ray, ray_t: &Interval, point: Vector3<f32>, normal: Vector3<f32>) -> Option<f32>
+{
+    let denom = dot(normal, ray.direction());
+    if denom.abs() < 1e-8
+    {
+        return None;
+    }
+
+    let t = dot(point - ray.origin(), normal) / denom;
+    if ray_t.contains(t) { Some(t) } else { None }
+}
+
+// Infinite plane. Its bounding box is unbounded, so keep it out of BVHs and add
+// it to the top-level list directly. (u, v) are world-unit coordinates in the plane.
+pub struct Plane
+{
+    _point: Vector3<f32>,
+    _normal: Vector3<f32>,
+    _tangent: Vector3<f32>,
+    _bitangent: Vector3<f32>,
+    _material: Arc<dyn Material>
+}
+
+impl Plane
+{
+    pub fn new(point: Vector3<f32>, normal: Vector3<f32>, material: Arc<dyn Material>) -> Self
+    {
+        let normal = unit_vector3(normal);
+        let (tangent, bitangent) = orthonormal_basis(normal);
+        Plane
+        {
+            _point: point,
+            _normal: normal,
+            _tangent: tangent,
+            _bitangent: bitangent,
+            _material: material
+        }
+    }
+}
+
+impl Hittable for Plane
+{
+    fn hit(&self, ray: Ray, ray_t: Interval) -> Option<HitRecord>
+    {
+        let t = plane_hit(&ray, &ray_t, self._point, self._normal)?;
+        let point = ray.at(t);
+        let offset = point - self._point;
+
+        let (normal, front_face) = set_face_normal(ray, self._normal);
+        Some(HitRecord{ _t: t, _point: point, _normal: normal, _material: self._material.clone(), _u: dot(offset, self._tangent), _v: dot(offset, self._bitangent), _front_face: front_face })
+    }
+
+    fn bounding_box(&self) -> Aabb
+    {
+        Aabb::new(Interval::universe(), Interval::universe(), Interval::universe())
+    }
+}
+
+// Flat disk facing along `normal`. u is the angle around the centre, v the
+// distance from it, both in [0, 1].
+pub struct Disk
+{
+    _center: Vector3<f32>,
+    _normal: Vector3<f32>,
+    _radius: f32,
+    _tangent: Vector3<f32>,
+    _bitangent: Vector3<f32>,
+    _material: Arc<dyn Material>,
+    _bbox: Aabb
+}
+
+impl Disk
+{
+    pub fn new(center: Vector3<f32>, normal: Vector3<f32>, radius: f32, material: Arc<dyn Material>) -> Self
+    {
+        let normal = unit_vector3(normal);
+        let (tangent, bitangent) = orthonormal_basis(normal);
+
+        // Extent along each axis is the radius times the length of the in-plane part of that axis
+        let extent = Vector3::new((1.0 - normal.x * normal.x).max(0.0).sqrt(),
+                                  (1.0 - normal.y * normal.y).max(0.0).sqrt(),
+                                  (1.0 - normal.z * normal.z).max(0.0).sqrt()) * radius;
+        Disk
+        {
+            _center: center,
+            _normal: normal,
+            _radius: radius,
+            _tangent: tangent,
+            _bitangent: bitangent,
+            _material: material,
+            _bbox: Aabb::from_points(center - extent, center + extent)
+        }
+    }
+}
+
+impl Hittable for Disk
+{
+    fn hit(&self, ray: Ray, ray_t: Interval) -> Option<HitRecord>
+    {
+        let t = plane_hit(&ray, &ray_t, self._center, self._normal)?;
+        let point = ray.at(t);
+        let offset = point - self._center;
+        let r2 = dot(offset, offset);
+        if r2 > self._radius * self._radius
+        {
+            return None;
+        }
+
+        let phi = dot(offset, self._bitangent).atan2(dot(offset, self._tangent));
+        let u = phi / (2.0 * PI) + 0.5;
+        let v = r2.sqrt() / self._radius;
+
+        let (normal, front_face) = set_face_normal(ray, self._normal);
+        Some(HitRecord{ _t: t, _point: point, _normal: normal, _material: self._material.clone(), _u: u, _v: v, _front_face: front_face })
+    }
+
+    fn bounding_box(&self) -> Aabb
+    {
+        self._bbox
+    }
+
+    // Uniform over the area, as for `Quad`
+    fn pdf_value(&self, origin: Vector3<f32>, direction: Vector3<f32>) -> f32
+    {
+        let rec = match self.hit(Ray::new(origin, direction), Interval::new(0.001, f32::INFINITY))
+        {
+            Some(rec) => rec,
+            None => return 0.0
+        };
+
+        let distance_squared = rec._t * rec._t * length_squared(direction);
+        let cosine = (dot(direction, self._normal) / length(direction)).abs();
+        distance_squared / (cosine * PI * self._radius * self._radius)
+    }
+
+    fn random(&self, origin: Vector3<f32>) -> Vector3<f32>
+    {
+        let p = self._radius * random_in_unit_disk();
+        self._center + p.x * self._tangent + p.y * self._bitangent - origin
+    }
+}
+
+#[cfg(test)]
+mod test
+{
+    use cgmath::{Array, InnerSpace};
+    use super::*;
+    use crate::renderer::material::Lambertian;
+
+    #[test]
+    fn test_plane_and_disk()
+    {
+        let material = Arc::new(Lambertian::new(Vector3::from_value(0.5)));
+        let plane = Plane::new(Vector3::new(0.0, -1.0, 0.0), Vector3::new(0.0, 1.0, 0.0), material.clone());
+        let disk = Disk::new(Vector3::new(0.0, -1.0, 0.0), Vector3::new(0.0, 1.0, 0.0), 2.0, material);
+        let ray_t = Interval::new(0.001, f32::INFINITY);
+
+        let far = Ray::new(Vector3::new(100.0, 1.0, 0.0), Vector3::new(0.0, -1.0, 0.0));
+        let rec = plane.hit(far, ray_t).unwrap();
+        assert!((rec.get_t() - 2.0).abs() < 1e-5);
+        assert!(rec._front_face);
+        assert!(disk.hit(far, ray_t).is_none());
+
+        let near = Ray::new(Vector3::new(1.0, -3.0, 0.0), Vector3::new(0.0, 1.0, 0.0));
+        let rec = disk.hit(near, ray_t).unwrap();
+        assert!(!rec._front_face);
+        assert!((rec.get_normal() - Vector3::new(0.0, -1.0, 0.0)).magnitude() < 1e-6);
+        assert!((rec._v - 0.5).abs() < 1e-5);
+
+        assert!((disk.bounding_box().max() - Vector3::new(2.0, -1.0, 2.0)).magnitude() < 1e-3);
+    }
+}
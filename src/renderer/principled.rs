@@ -0,0 +1,442 @@
+use std::sync::Arc;
+use cgmath::{dot, Array, Vector3};
+use crate::renderer::custom_function::{random_cosine_direction, random_double, unit_vector3};
+use crate::renderer::hittable::HitRecord;
+use crate::renderer::ior_stack::Interior;
+use crate::renderer::material::Material;
+use crate::renderer::microfacet::{distinct_eta, fresnel_dielectric, reflect_about, refract_about, Ggx, ShadingFrame};
+use crate::renderer::ray::Ray;
+use crate::renderer::texture::{SolidColor, Texture};
+use crate::utility::constants::PI;
+
+// Disney's principled BSDF (Burley 2012, with the 2015 transmission extension),
+// the parameter set DCC tools export. All parameters are in [0, 1] except
+// `anisotropy`, which is in [-1, 1]. Built from a base colour with one setter
+// per parameter:
+//
+//     Principled::new(Vector3::new(0.8, 0.1, 0.1)).roughness(0.4).clearcoat(1.0)
+//
+// Lobes: Burley diffuse with retro-reflection and sheen, a GGX specular lobe,
+// a GTR1 clearcoat and GGX refraction. There is no subsurface lobe. `specular`
+// sets the dielectric reflectance at normal incidence to 0.08 * specular, so
+// the default 0.5 is 4% or an IOR of 1.5; refraction uses the same IOR, and
+// transmissive materials bound a medium of it like `Dielectric` does.
+#[derive(Clone)]
+pub struct Principled
+{
+    base_color: Arc<dyn Texture>,
+    metallic: f32,
+    roughness: f32,
+    anisotropy: f32,
+    specular: f32,
+    specular_tint: f32,
+    sheen: f32,
+    sheen_tint: f32,
+    clearcoat: f32,
+    clearcoat_gloss: f32,
+    transmission: f32,
+    // Nesting priority of the inside of transmissive objects, see `Dielectric::with_priority`
+    priority: u32
+}
+
+// Weight of the Schlick Fresnel approximation at `cosine`
+fn schlick_weight(cosine: f32) -> f32
+{
+    (1.0 - cosine.clamp(0.0, 1.0)).powi(5)
+}
+
+fn mix(a: Vector3<f32>, b: Vector3<f32>, t: f32) -> Vector3<f32>
+{
+    a + (b - a) * t
+}
+
+// Clearcoat distribution, Berry's GTR with gamma = 1
+fn gtr1(cos_h: f32, alpha: f32) -> f32
+{
+    let a2 = alpha * alpha;
+    let t = 1.0 + (a2 - 1.0) * cos_h * cos_h;
+    (a2 - 1.0) / (PI * a2.ln() * t)
+}
+
+// Everything at one hit that doesn't depend on the light direction
+struct Lobes
+{
+    base: Vector3<f32>,
+    // Tint of the dielectric reflection and of the sheen
+    specular_tint: Vector3<f32>,
+    sheen_tint: Vector3<f32>,
+    eta: f32,
+    specular: Ggx,
+    clearcoat_alpha: f32,
+    // Weights of the diffuse, clearcoat and refraction lobes
+    diffuse: f32,
+    clearcoat: f32,
+    transmission: f32,
+    // Probabilities of sampling diffuse, specular, clearcoat and refraction
+    p: [f32; 4]
+}
+
+impl Principled
+{
+    pub fn new(base_color: Vector3<f32>) -> Self
+    {
+        Principled::from_texture(Arc::new(SolidColor::new(base_color)))
+    }
+
+    pub fn from_texture(texture: Arc<dyn Texture>) -> Self
+    {
+        Principled
+        {
+            base_color: texture,
+            metallic: 0.0,
+            roughness: 0.5,
+            anisotropy: 0.0,
+            specular: 0.5,
+            specular_tint: 0.0,
+            sheen: 0.0,
+            sheen_tint: 0.5,
+            clearcoat: 0.0,
+            clearcoat_gloss: 1.0,
+            transmission: 0.0,
+            priority: 0
+        }
+    }
+
+    pub fn metallic(mut self, metallic: f32) -> Self
+    {
+        self.metallic = metallic.clamp(0.0, 1.0);
+        self
+    }
+
+    pub fn roughness(mut self, roughness: f32) -> Self
+    {
+        self.roughness = roughness.clamp(0.0, 1.0);
+        self
+    }
+
+    pub fn anisotropy(mut self, anisotropy: f32) -> Self
+    {
+        self.anisotropy = anisotropy.clamp(-1.0, 1.0);
+        self
+    }
+
+    pub fn specular(mut self, specular: f32) -> Self
+    {
+        self.specular = specular.clamp(0.0, 1.0);
+        self
+    }
+
+    pub fn specular_tint(mut self, specular_tint: f32) -> Self
+    {
+        self.specular_tint = specular_tint.clamp(0.0, 1.0);
+        self
+    }
+
+    pub fn sheen(mut self, sheen: f32) -> Self
+    {
+        self.sheen = sheen.clamp(0.0, 1.0);
+        self
+    }
+
+    pub fn sheen_tint(mut self, sheen_tint: f32) -> Self
+    {
+        self.sheen_tint = sheen_tint.clamp(0.0, 1.0);
+        self
+    }
+
+    pub fn clearcoat(mut self, clearcoat: f32) -> Self
+    {
+        self.clearcoat = clearcoat.clamp(0.0, 1.0);
+        self
+    }
+
+    pub fn clearcoat_gloss(mut self, clearcoat_gloss: f32) -> Self
+    {
+        self.clearcoat_gloss = clearcoat_gloss.clamp(0.0, 1.0);
+        self
+    }
+
+    pub fn transmission(mut self, transmission: f32) -> Self
+    {
+        self.transmission = transmission.clamp(0.0, 1.0);
+        self
+    }
+
+    pub fn priority(mut self, priority: u32) -> Self
+    {
+        self.priority = priority;
+        self
+    }
+
+    fn ior(&self) -> f32
+    {
+        let f0 = (0.08 * self.specular).sqrt();
+        (1.0 + f0) / (1.0 - f0)
+    }
+
+    fn lobes(&self, r_in: &Ray, rec: &HitRecord) -> Lobes
+    {
+        let base = self.base_color.value(rec._u, rec._v, rec.get_point());
+        let luminance = 0.3 * base.x + 0.6 * base.y + 0.1 * base.z;
+        let hue = if luminance > 0.0 { base / luminance } else { Vector3::from_value(1.0) };
+
+        let ior = self.ior();
+        let eta = if rec._front_face { ior / r_in.surrounding_ior() } else { r_in.surrounding_ior() / ior };
+
+        // Seen from inside only the dielectric interface is left, the diffuse and
+        // clearcoat layers sit on the outside
+        let outside = if rec._front_face { 1.0 } else { 0.0 };
+        let dielectric = 1.0 - self.metallic;
+        let diffuse = outside * dielectric * (1.0 - self.transmission);
+        let clearcoat = outside * 0.25 * self.clearcoat;
+        let transmission = dielectric * self.transmission;
+
+        let total = diffuse + 1.0 + clearcoat + transmission;
+        Lobes
+        {
+            base,
+            specular_tint: mix(Vector3::from_value(1.0), hue, self.specular_tint),
+            sheen_tint: mix(Vector3::from_value(1.0), hue, self.sheen_tint),
+            eta: distinct_eta(eta),
+            specular: Ggx::new(self.roughness, self.anisotropy),
+            clearcoat_alpha: 0.1 + (0.001 - 0.1) * self.clearcoat_gloss,
+            diffuse,
+            clearcoat,
+            transmission,
+            p: [diffuse / total, 1.0 / total, clearcoat / total, transmission / total]
+        }
+    }
+
+    // Reflectance of the specular lobe, dielectric or metal
+    fn fresnel(&self, lobes: &Lobes, cos_d: f32) -> Vector3<f32>
+    {
+        let dielectric = lobes.specular_tint * fresnel_dielectric(cos_d, lobes.eta);
+        let metal = mix(lobes.base, Vector3::from_value(1.0), schlick_weight(cos_d));
+        mix(dielectric, metal, self.metallic)
+    }
+
+    // Microfacet normal of a refraction from `wo` into `wi`, with the denominator
+    // of the change of variables, as in `RoughDielectric`
+    fn refraction_half_vector(wo: Vector3<f32>, wi: Vector3<f32>, eta: f32) -> Option<(Vector3<f32>, f32)>
+    {
+        let mut h = unit_vector3(wo + eta * wi);
+        if h.z < 0.0
+        {
+            h = -h;
+        }
+        if dot(wo, h) <= 0.0 || dot(wi, h) >= 0.0
+        {
+            return None;
+        }
+        Some((h, dot(wo, h) + eta * dot(wi, h)))
+    }
+
+    fn eval_local(&self, lobes: &Lobes, wo: Vector3<f32>, wi: Vector3<f32>) -> Vector3<f32>
+    {
+        let mut f = Vector3::from_value(0.0);
+        if wo.z <= 0.0
+        {
+            return f;
+        }
+
+        if wi.z > 0.0
+        {
+            let h = unit_vector3(wo + wi);
+            let cos_d = dot(wi, h);
+
+            if lobes.diffuse > 0.0
+            {
+                let fd90 = 0.5 + 2.0 * self.roughness * cos_d * cos_d;
+                let retro = (1.0 + (fd90 - 1.0) * schlick_weight(wi.z)) * (1.0 + (fd90 - 1.0) * schlick_weight(wo.z));
+                let sheen = lobes.sheen_tint * (self.sheen * schlick_weight(cos_d));
+                f += (lobes.base * (retro / PI) + sheen) * (lobes.diffuse * wi.z);
+            }
+
+            let ggx = &lobes.specular;
+            f += self.fresnel(lobes, cos_d) * (ggx.d(h) * ggx.g(wo, wi) / (4.0 * wo.z));
+
+            if lobes.clearcoat > 0.0
+            {
+                let fresnel = 0.04 + 0.96 * schlick_weight(cos_d);
+                let shadowing = Ggx::new(0.5, 0.0).g(wo, wi);
+                f += Vector3::from_value(lobes.clearcoat * fresnel * gtr1(h.z, lobes.clearcoat_alpha) * shadowing / (4.0 * wo.z));
+            }
+        }
+        else if wi.z < 0.0 && lobes.transmission > 0.0
+        {
+            if let Some((h, denom)) = Principled::refraction_half_vector(wo, wi, lobes.eta)
+            {
+                let ggx = &lobes.specular;
+                let fresnel = fresnel_dielectric(dot(wo, h), lobes.eta);
+                let eta2 = lobes.eta * lobes.eta;
+                let value = (1.0 - fresnel) * ggx.d(h) * ggx.g(wo, wi) * eta2 * dot(wi, h).abs() * dot(wo, h) / (wo.z * denom * denom);
+                // Tinted by the square root on each crossing, by the base colour in and out
+                let tint = Vector3::new(lobes.base.x.sqrt(), lobes.base.y.sqrt(), lobes.base.z.sqrt());
+                f += tint * (lobes.transmission * value);
+            }
+        }
+
+        f
+    }
+
+    fn pdf_local(&self, lobes: &Lobes, wo: Vector3<f32>, wi: Vector3<f32>) -> f32
+    {
+        if wo.z <= 0.0
+        {
+            return 0.0;
+        }
+
+        let [p_diffuse, p_specular, p_clearcoat, p_transmission] = lobes.p;
+        let mut pdf = 0.0;
+        if wi.z > 0.0
+        {
+            let h = unit_vector3(wo + wi);
+            pdf += p_diffuse * wi.z / PI;
+            pdf += p_specular * lobes.specular.visible_pdf(wo, h) / (4.0 * dot(wo, h));
+            pdf += p_clearcoat * gtr1(h.z, lobes.clearcoat_alpha) * h.z / (4.0 * dot(wo, h));
+        }
+        else if wi.z < 0.0 && p_transmission > 0.0
+        {
+            if let Some((h, denom)) = Principled::refraction_half_vector(wo, wi, lobes.eta)
+            {
+                pdf += p_transmission * lobes.specular.visible_pdf(wo, h) * lobes.eta * lobes.eta * dot(wi, h).abs() / (denom * denom);
+            }
+        }
+        pdf
+    }
+
+    // Picks a lobe, then a direction from it
+    fn sample_local(&self, lobes: &Lobes, wo: Vector3<f32>) -> Option<Vector3<f32>>
+    {
+        let [p_diffuse, p_specular, p_clearcoat, _] = lobes.p;
+        let u = random_double();
+
+        if u < p_diffuse
+        {
+            Some(random_cosine_direction())
+        }
+        else if u < p_diffuse + p_specular
+        {
+            Some(reflect_about(wo, lobes.specular.sample_visible(wo, random_double(), random_double())))
+        }
+        else if u < p_diffuse + p_specular + p_clearcoat
+        {
+            let a2 = lobes.clearcoat_alpha * lobes.clearcoat_alpha;
+            let cos_theta = ((1.0 - a2.powf(1.0 - random_double())) / (1.0 - a2)).max(0.0).sqrt();
+            let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+            let phi = 2.0 * PI * random_double();
+            let h = Vector3::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta);
+            Some(reflect_about(wo, h))
+        }
+        else
+        {
+            let h = lobes.specular.sample_visible(wo, random_double(), random_double());
+            refract_about(wo, h, lobes.eta)
+        }
+    }
+}
+
+impl Material for Principled
+{
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, attenuation: &mut Vector3<f32>, scattered: &mut Ray) -> bool
+    {
+        let frame = ShadingFrame::new(rec.get_normal());
+        let wo = frame.to_local(-unit_vector3(r_in.direction()));
+        let lobes = self.lobes(r_in, rec);
+
+        let wi = match self.sample_local(&lobes, wo)
+        {
+            Some(wi) => wi,
+            None => return false
+        };
+        // Every lobe could have produced the direction, so the density is that of the mixture
+        let pdf = self.pdf_local(&lobes, wo, wi);
+        if pdf <= 0.0
+        {
+            return false;
+        }
+
+        scattered.clone_from(&Ray::with_time(rec.get_point(), frame.to_world(wi), r_in.time()));
+        attenuation.clone_from(&(self.eval_local(&lobes, wo, wi) / pdf));
+        true
+    }
+
+    fn scattering_pdf(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f32
+    {
+        let frame = ShadingFrame::new(rec.get_normal());
+        let wo = frame.to_local(-unit_vector3(r_in.direction()));
+        let wi = frame.to_local(unit_vector3(scattered.direction()));
+        self.pdf_local(&self.lobes(r_in, rec), wo, wi)
+    }
+
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Vector3<f32>
+    {
+        let frame = ShadingFrame::new(rec.get_normal());
+        let wo = frame.to_local(-unit_vector3(r_in.direction()));
+        let wi = frame.to_local(unit_vector3(scattered.direction()));
+        self.eval_local(&self.lobes(r_in, rec), wo, wi)
+    }
+
+    // Only objects light can pass through bound a medium
+    fn interior(&self, _wavelength: Option<f32>) -> Option<Interior>
+    {
+        if self.transmission * (1.0 - self.metallic) > 0.0
+        {
+            Some(Interior { ior: self.ior(), priority: self.priority, absorption: Vector3::from_value(0.0) })
+        }
+        else
+        {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod test
+{
+    use super::*;
+    use crate::renderer::custom_function::random_unit_vector3;
+    use crate::renderer::sampler::seed_thread_sampler;
+
+    // Mean attenuation of `scatter` against a uniform estimate of the integral of `eval`
+    fn albedo(material: Principled, front_face: bool) -> (Vector3<f32>, Vector3<f32>)
+    {
+        seed_thread_sampler(11, 0, 0);
+        let material: Arc<dyn Material> = Arc::new(material);
+        let rec = HitRecord{ _t: 1.0, _point: Vector3::from_value(0.0), _normal: Vector3::new(0.0, 0.0, 1.0), _material: material.clone(), _u: 0.0, _v: 0.0, _front_face: front_face };
+        let r_in = Ray::new(Vector3::new(-0.5, 0.3, 0.8), Vector3::new(0.5, -0.3, -0.8));
+
+        let n = 200_000;
+        let mut sampled = Vector3::from_value(0.0);
+        let mut uniform = Vector3::from_value(0.0);
+        let mut attenuation = Vector3::from_value(0.0);
+        let mut scattered = r_in;
+        for _ in 0..n
+        {
+            if material.scatter(&r_in, &rec, &mut attenuation, &mut scattered)
+            {
+                sampled += attenuation;
+            }
+            uniform += material.eval(&r_in, &rec, &Ray::new(Vector3::from_value(0.0), random_unit_vector3())) * (4.0 * PI);
+        }
+        (sampled / n as f32, uniform / n as f32)
+    }
+
+    #[test]
+    fn test_lobes_sample_their_own_density()
+    {
+        let materials = [
+            (Principled::new(Vector3::new(0.8, 0.2, 0.1)).roughness(0.6).sheen(1.0), true),
+            (Principled::new(Vector3::new(0.9, 0.6, 0.3)).metallic(1.0).roughness(0.5).anisotropy(0.5), true),
+            (Principled::new(Vector3::new(0.2, 0.3, 0.8)).roughness(0.5).clearcoat(1.0).clearcoat_gloss(0.3), true),
+            (Principled::new(Vector3::new(0.9, 0.9, 0.9)).roughness(0.5).transmission(1.0), true),
+            (Principled::new(Vector3::new(0.9, 0.9, 0.9)).roughness(0.5).transmission(1.0), false)
+        ];
+        for (material, front_face) in materials.iter().cloned()
+        {
+            let (sampled, uniform) = albedo(material, front_face);
+            assert!((sampled - uniform).map(f32::abs).sum() < 0.05, "{:?} vs {:?}", sampled, uniform);
+            assert!(sampled.x < 1.05 && sampled.x > 0.1);
+        }
+    }
+}
@@ -0,0 +1,229 @@
+use std::sync::Arc;
+use cgmath::{dot, Vector3};
+use crate::renderer::aabb::Aabb;
+use crate::renderer::custom_function::{length, length_squared, random_double, set_face_normal, unit_vector3};
+use crate::renderer::hittable::{HitRecord, Hittable};
+use crate::renderer::hittable_list::HittableList;
+use crate::renderer::interval::Interval;
+use crate::renderer::material::Material;
+use crate::renderer::ray::Ray;
+
+// Parallelogram with corner `q` and edges `u` and `v`. The front side is the one
+// `u x v` points to; (u, v) texture coordinates run from 0 to 1 along the edges.
+pub struct Quad
+{
+    _q: Vector3<f32>,
+    _u: Vector3<f32>,
+    _v: Vector3<f32>,
+    // u x v scaled by 1 / |u x v|^2, turns plane offsets into edge coordinates
+    _w: Vector3<f32>,
+    _normal: Vector3<f32>,
+    _d: f32,
+    _area: f32,
+    _material: Arc<dyn Material>,
+    _bbox: Aabb
+}
+
+impl Quad
+{
+    pub fn new(q: Vector3<f32>, u: Vector3<f32>, v: Vector3<f32>, material: Arc<dyn Material>) -> Self
+    {
+        let n = u.cross(v);
+        let normal = unit_vector3(n);
+        let bbox_diagonal1 = Aabb::from_points(q, q + u + v);
+        let bbox_diagonal2 = Aabb::from_points(q + u, q + v);
+
+        Quad
+        {
+            _q: q,
+            _u: u,
+            _v: v,
+            _w: n / dot(n, n),
+            _normal: normal,
+            _d: dot(normal, q),
+            _area: length(n),
+            _material: material,
+            _bbox: Aabb::surrounding(&bbox_diagonal1, &bbox_diagonal2)
+        }
+    }
+}
+
+impl Hittable for Quad
+{
+    fn hit(&self, ray: Ray, ray_t: Interval) -> Option<HitRecord>
+    {
+        // No hit if the ray is parallel to the plane
+        let denom = dot(self._normal, ray.direction());
+        if denom.abs() < 1e-8
+        {
+            return None;
+        }
+
+        let t = (self._d - dot(self._normal, ray.origin())) / denom;
+        if !ray_t.contains(t)
+        {
+            return None;
+        }
+
+        // Edge coordinates of the hit point inside the plane
+        let intersection = ray.at(t);
+        let planar_hitpt = intersection - self._q;
+        let alpha = dot(self._w, planar_hitpt.cross(self._v));
+        let beta = dot(self._w, self._u.cross(planar_hitpt));
+
+        let unit = Interval::new(0.0, 1.0);
+        if !unit.contains(alpha) || !unit.contains(beta)
+        {
+            return None;
+        }
+
+        let (normal, front_face) = set_face_normal(ray, self._normal);
+        Some(HitRecord{ _t: t, _point: intersection, _normal: normal, _material: self._material.clone(), _u: alpha, _v: beta, _front_face: front_face })
+    }
+
+    fn bounding_box(&self) -> Aabb
+    {
+        self._bbox
+    }
+
+    // Uniform over the area, converted to solid angle by distance^2 / cos
+    fn pdf_value(&self, origin: Vector3<f32>, direction: Vector3<f32>) -> f32
+    {
+        let rec = match self.hit(Ray::new(origin, direction), Interval::new(0.001, f32::INFINITY))
+        {
+            Some(rec) => rec,
+            None => return 0.0
+        };
+
+        let distance_squared = rec._t * rec._t * length_squared(direction);
+        let cosine = (dot(direction, self._normal) / length(direction)).abs();
+        distance_squared / (cosine * self._area)
+    }
+
+    fn random(&self, origin: Vector3<f32>) -> Vector3<f32>
+    {
+        let p = self._q + random_double() * self._u + random_double() * self._v;
+        p - origin
+    }
+}
+
+// Closed box made of six quads whose normals all point outwards
+pub struct Cuboid
+{
+    _sides: HittableList
+}
+
+impl Cuboid
+{
+    // Axis-aligned box with opposite corners `a` and `b`
+    pub fn new(a: Vector3<f32>, b: Vector3<f32>, material: Arc<dyn Material>) -> Self
+    {
+        let min = Vector3::new(a.x.min(b.x), a.y.min(b.y), a.z.min(b.z));
+        let max = Vector3::new(a.x.max(b.x), a.y.max(b.y), a.z.max(b.z));
+        let size = max - min;
+
+        Cuboid::oriented((min + max) * 0.5,
+                         [Vector3::new(size.x, 0.0, 0.0), Vector3::new(0.0, size.y, 0.0), Vector3::new(0.0, 0.0, size.z)],
+                         material)
+    }
+
+    // Box around `center` spanned by three perpendicular full-length edges
+    pub fn oriented(center: Vector3<f32>, edges: [Vector3<f32>; 3], material: Arc<dyn Material>) -> Self
+    {
+        let [mut a, mut b, c] = edges;
+        // Outward normals below rely on a right-handed edge set
+        if dot(a.cross(b), c) < 0.0
+        {
+            std::mem::swap(&mut a, &mut b);
+        }
+
+        let p = center - (a + b + c) * 0.5;
+        let mut sides = HittableList::new();
+        sides.add(Arc::new(Quad::new(p + c, a, b, material.clone())));          // front  (+c)
+        sides.add(Arc::new(Quad::new(p + a, -a, b, material.clone())));         // back   (-c)
+        sides.add(Arc::new(Quad::new(p + a + c, -c, b, material.clone())));     // right  (+a)
+        sides.add(Arc::new(Quad::new(p, c, b, material.clone())));              // left   (-a)
+        sides.add(Arc::new(Quad::new(p + b + c, a, -c, material.clone())));     // top    (+b)
+        sides.add(Arc::new(Quad::new(p, a, c, material)));                      // bottom (-b)
+
+        Cuboid
+        {
+            _sides: sides
+        }
+    }
+}
+
+impl Hittable for Cuboid
+{
+    fn hit(&self, ray: Ray, ray_t: Interval) -> Option<HitRecord>
+    {
+        self._sides.hit(ray, ray_t)
+    }
+
+    fn bounding_box(&self) -> Aabb
+    {
+        self._sides.bounding_box()
+    }
+
+    fn pdf_value(&self, origin: Vector3<f32>, direction: Vector3<f32>) -> f32
+    {
+        self._sides.pdf_value(origin, direction)
+    }
+
+    fn random(&self, origin: Vector3<f32>) -> Vector3<f32>
+    {
+        self._sides.random(origin)
+    }
+}
+
+#[cfg(test)]
+mod test
+{
+    use cgmath::{Array, InnerSpace};
+    use super::*;
+    use crate::renderer::material::Lambertian;
+
+    fn material() -> Arc<dyn Material>
+    {
+        Arc::new(Lambertian::new(Vector3::from_value(0.5)))
+    }
+
+    #[test]
+    fn test_quad_hit_and_uv()
+    {
+        let quad = Quad::new(Vector3::new(0.0, 0.0, 0.0), Vector3::new(2.0, 0.0, 0.0), Vector3::new(0.0, 1.0, 0.0), material());
+
+        let rec = quad.hit(Ray::new(Vector3::new(0.5, 0.25, 1.0), Vector3::new(0.0, 0.0, -1.0)), Interval::new(0.001, f32::INFINITY)).unwrap();
+        assert!((rec._u - 0.25).abs() < 1e-6 && (rec._v - 0.25).abs() < 1e-6);
+        assert!(rec._front_face);
+
+        let back = quad.hit(Ray::new(Vector3::new(0.5, 0.25, -1.0), Vector3::new(0.0, 0.0, 1.0)), Interval::new(0.001, f32::INFINITY)).unwrap();
+        assert!(!back._front_face);
+        assert_eq!(back.get_normal(), Vector3::new(0.0, 0.0, -1.0));
+
+        assert!(quad.hit(Ray::new(Vector3::new(2.5, 0.25, 1.0), Vector3::new(0.0, 0.0, -1.0)), Interval::new(0.001, f32::INFINITY)).is_none());
+    }
+
+    #[test]
+    fn test_cuboid_normals_point_outwards()
+    {
+        let cuboid = Cuboid::new(Vector3::new(-1.0, -1.0, -1.0), Vector3::new(1.0, 1.0, 1.0), material());
+        let axes = [Vector3::new(1.0, 0.0, 0.0), Vector3::new(0.0, 1.0, 0.0), Vector3::new(0.0, 0.0, 1.0)];
+
+        for &axis in &axes
+        {
+            for &sign in &[1.0, -1.0]
+            {
+                let outward = axis * sign;
+                let rec = cuboid.hit(Ray::new(outward * 5.0, -outward), Interval::new(0.001, f32::INFINITY)).unwrap();
+                assert!(rec._front_face);
+                assert!((rec.get_normal() - outward).magnitude() < 1e-6);
+                assert!((rec.get_t() - 4.0).abs() < 1e-5);
+
+                // From inside the same face is hit on its back
+                let inside = cuboid.hit(Ray::new(Vector3::from_value(0.0), outward), Interval::new(0.001, f32::INFINITY)).unwrap();
+                assert!(!inside._front_face);
+            }
+        }
+    }
+}
@@ -4,19 +4,40 @@ use cgmath::{Vector3};
 pub struct Ray
 {
     _origin:                    Vector3<f32>,
-    _direction:                 Vector3<f32>
+    _direction:                 Vector3<f32>,
+    // Instant within the shutter interval, moving objects are placed at this time
+    _time:                      f32,
+    // Hero wavelength in nanometres on spectral paths, `None` when rendering RGB
+    _wavelength:                Option<f32>,
+    // Index of refraction beyond the dielectric surface the ray hits next, see `IorStack`
+    _surrounding_ior:           f32
 }
 
 impl Ray {
     pub fn new(ori: Vector3<f32>, dir: Vector3<f32>) -> Ray
+    {
+        Ray::with_time(ori, dir, 0.0)
+    }
+
+    pub fn with_time(ori: Vector3<f32>, dir: Vector3<f32>, time: f32) -> Ray
     {
         Ray
         {
             _origin: ori,
-            _direction: dir
+            _direction: dir,
+            _time: time,
+            _wavelength: None,
+            _surrounding_ior: 1.0
         }
     }
 
+    // The same ray on the given wavelength
+    pub fn with_wavelength(mut self, wavelength: Option<f32>) -> Ray
+    {
+        self._wavelength = wavelength;
+        self
+    }
+
     pub fn origin(&self) -> Vector3<f32>
     {
         self._origin
@@ -27,6 +48,28 @@ impl Ray {
         self._direction
     }
 
+    pub fn time(&self) -> f32
+    {
+        self._time
+    }
+
+    pub fn wavelength(&self) -> Option<f32>
+    {
+        self._wavelength
+    }
+
+    // The same ray with the medium around the next surface set
+    pub fn with_surrounding_ior(mut self, ior: f32) -> Ray
+    {
+        self._surrounding_ior = ior;
+        self
+    }
+
+    pub fn surrounding_ior(&self) -> f32
+    {
+        self._surrounding_ior
+    }
+
     pub fn at(&self, t: f32) -> Vector3<f32>
     {
         self._origin + self._direction * t
@@ -1,33 +1,63 @@
-use std::rc::Rc;
+use std::path::Path;
 use std::sync::Arc;
-use cgmath::{Array, Vector3, Vector4};
+use cgmath::{Vector3, Vector4};
+use crate::renderer::bvh::BvhNode;
 use crate::renderer::hittable_list::HittableList;
 use super::camera;
+use crate::renderer::scene::Scene;
+use crate::renderer::scene_file::{load_scene, SceneError};
+use crate::renderer::settings::{RenderArgs, RenderSettings};
 use crate::renderer::sphere::Sphere;
 use crate::renderer::material::{Dielectric, Lambertian, Metal};
-use crate::utility::constants::PI;
 
-pub fn render(w: u32, h: u32, u: u32, v: u32) -> Vector4<f32>
+pub fn build_scene(w: u32, h: u32) -> Scene
  {
      // Camera
-     let cam = camera::Camera::new(Vector3::new(-2.0, 2.0, 1.0),
-                                   Vector3::new(0.0, 0.0, -1.0), 16.0 / 9.0,
-                                    w, h, 20.0);
+     let cam = camera::Camera::builder(Vector3::new(-2.0, 2.0, 1.0), Vector3::new(0.0, 0.0, -1.0))
+                                   .vfov(20.0)
+                                   .defocus_angle(10.0)
+                                   .focus_dist(3.4)
+                                   .build()
+                                   .with_settings(&RenderSettings { width: w, height: h, ..RenderSettings::default() });
 
      // Metarial
      let material_ground = Lambertian::new(Vector3::new(0.8, 0.8, 0.0));
      let material_center = Lambertian::new(Vector3::new(0.1, 0.2, 0.5));
      let material_left = Dielectric::new(1.5);
-     let material_left2 = Dielectric::new(1.5);
+     // Air bubble inside the glass ball, the higher priority makes it win where they overlap
+     let material_bubble = Dielectric::new(1.0).with_priority(1);
      let material_right = Metal::new(Vector3::new(0.8, 0.6, 0.2), 0.0);
 
      // World
+     let mut objects = HittableList::new();
+     objects.add(Arc::new(Sphere::new(Vector3::new(0.0, 0.0, -1.0), 0.5, Arc::new(material_center))));
+     objects.add(Arc::new(Sphere::new(Vector3::new(0.0, -100.5, -1.0), 100.0, Arc::new(material_ground))));
+     objects.add(Arc::new(Sphere::new(Vector3::new(-1.0, 0.0, -1.0), 0.5, Arc::new(material_left))));
+     objects.add(Arc::new(Sphere::new(Vector3::new(-1.0, 0.0, -1.0), 0.4, Arc::new(material_bubble))));
+     objects.add(Arc::new(Sphere::new(Vector3::new(1.0, 0.0, -1.0), 0.5, Arc::new(material_right))));
+
      let mut world = HittableList::new();
-     world.add(Rc::new(Sphere::new(Vector3::new(0.0, 0.0, -1.0), 0.5, Arc::new(material_center))));
-     world.add(Rc::new(Sphere::new(Vector3::new(0.0, -100.5, -1.0), 100.0, Arc::new(material_ground))));
-     world.add(Rc::new(Sphere::new(Vector3::new(-1.0, 0.0, -1.0), 0.5, Arc::new(material_left))));
-     world.add(Rc::new(Sphere::new(Vector3::new(-1.0, 0.0, -1.0), -0.4, Arc::new(material_left2))));
-     world.add(Rc::new(Sphere::new(Vector3::new(1.0, 0.0, -1.0), 0.5, Arc::new(material_right))));
-
-     cam.render(w, h, u, v, world)
- }
\ No newline at end of file
+     world.add(Arc::new(BvhNode::new(objects)));
+
+     Scene::new(cam, world)
+ }
+
+// Loads the scene file when one is given, otherwise falls back to the built-in demo scene.
+// Settings come from the defaults, then the scene file, then the command line.
+pub fn load_or_build_scene(path: Option<&Path>, args: &RenderArgs) -> Result<(Scene, RenderSettings), SceneError>
+ {
+     let mut settings = RenderSettings::default();
+     let scene = match path
+     {
+         Some(path) => load_scene(path, &mut settings)?,
+         None => build_scene(settings.width, settings.height)
+     };
+     args.apply(&mut settings);
+
+     Ok((scene.with_settings(&settings), settings))
+ }
+
+pub fn render(scene: &Scene, w: u32, h: u32, u: u32, v: u32) -> Vector4<f32>
+ {
+     scene.render_pixel(w, h, u, v)
+ }
@@ -0,0 +1,111 @@
+use std::cell::Cell;
+
+// PCG32 (XSH-RR) random number generator, see https://www.pcg-random.org
+#[derive(Copy, Clone, Debug)]
+pub struct Sampler
+{
+    state: u64,
+    inc: u64
+}
+
+impl Sampler
+{
+    pub const fn new(seed: u64, stream: u64) -> Self
+    {
+        let mut sampler = Sampler
+        {
+            state: 0,
+            inc: (stream << 1) | 1
+        };
+        sampler.next_u32();
+        sampler.state = sampler.state.wrapping_add(seed);
+        sampler.next_u32();
+        sampler
+    }
+
+    // Independent sequence for one sample of one pixel under a global seed
+    pub fn for_sample(seed: u64, pixel: u64, sample: u64) -> Self
+    {
+        let key = splitmix64(splitmix64(seed ^ splitmix64(pixel)) ^ sample);
+        Sampler::new(key, splitmix64(key))
+    }
+
+    pub const fn next_u32(&mut self) -> u32
+    {
+        let old = self.state;
+        self.state = old.wrapping_mul(6364136223846793005).wrapping_add(self.inc);
+        let xorshifted = (((old >> 18) ^ old) >> 27) as u32;
+        let rot = (old >> 59) as u32;
+        xorshifted.rotate_right(rot)
+    }
+
+    // Uniform in [0, 1), uses the top 24 bits so the result is exactly representable
+    pub fn next_f32(&mut self) -> f32
+    {
+        (self.next_u32() >> 8) as f32 * (1.0 / 16777216.0)
+    }
+}
+
+fn splitmix64(x: u64) -> u64
+{
+    let mut z = x.wrapping_add(0x9E3779B97F4A7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+    z ^ (z >> 31)
+}
+
+// Every render thread owns one sampler. The camera reseeds it before each
+// sample, so results only depend on the seed and never on thread scheduling.
+// The const initializer keeps each access down to a plain load and store.
+thread_local!
+{
+    static THREAD_SAMPLER: Cell<Sampler> = const { Cell::new(Sampler::new(0, 0)) };
+}
+
+pub fn seed_thread_sampler(seed: u64, pixel: u64, sample: u64)
+{
+    THREAD_SAMPLER.with(|s| s.set(Sampler::for_sample(seed, pixel, sample)));
+}
+
+#[inline]
+pub fn next_f32() -> f32
+{
+    THREAD_SAMPLER.with(|s| {
+        let mut sampler = s.get();
+        let x = sampler.next_f32();
+        s.set(sampler);
+        x
+    })
+}
+
+#[cfg(test)]
+mod test
+{
+    use super::*;
+
+    #[test]
+    fn test_sampler_is_reproducible()
+    {
+        let mut a = Sampler::for_sample(42, 1234, 7);
+        let mut b = Sampler::for_sample(42, 1234, 7);
+        let mut c = Sampler::for_sample(43, 1234, 7);
+
+        let seq_a: Vec<u32> = (0..16).map(|_| a.next_u32()).collect();
+        let seq_b: Vec<u32> = (0..16).map(|_| b.next_u32()).collect();
+        let seq_c: Vec<u32> = (0..16).map(|_| c.next_u32()).collect();
+
+        assert_eq!(seq_a, seq_b);
+        assert_ne!(seq_a, seq_c);
+    }
+
+    #[test]
+    fn test_next_f32_range()
+    {
+        let mut s = Sampler::new(1, 1);
+        for _ in 0..10000
+        {
+            let x = s.next_f32();
+            assert!(x >= 0.0 && x < 1.0);
+        }
+    }
+}
@@ -0,0 +1,100 @@
+use std::sync::Arc;
+use cgmath::Vector4;
+use crate::renderer::background::{Background, GradientBackground};
+use crate::renderer::camera::Camera;
+use crate::renderer::hittable::Hittable;
+use crate::renderer::hittable_list::HittableList;
+use crate::renderer::settings::RenderSettings;
+
+// What a path can meet on its way: the objects, the emitters sampled directly at
+// every diffuse bounce (`None` when there are none) and the background
+#[derive(Copy, Clone)]
+pub struct SceneView<'a>
+{
+    pub world: &'a dyn Hittable,
+    pub lights: Option<&'a dyn Hittable>,
+    pub background: &'a dyn Background
+}
+
+// Everything needed to trace a frame. Built once and shared read-only
+// (behind an `Arc`) by all render threads.
+pub struct Scene
+{
+    _camera: Camera,
+    _world: HittableList,
+    // Emitters that are sampled directly, a subset of `_world`
+    _lights: HittableList,
+    _background: Arc<dyn Background>
+}
+
+impl Scene
+{
+    pub fn new(camera: Camera, world: HittableList) -> Self
+    {
+        Scene
+        {
+            _camera: camera.focused_on(&world),
+            _world: world,
+            _lights: HittableList::new(),
+            _background: Arc::new(GradientBackground::sky())
+        }
+    }
+
+    // Replaces the default sky gradient, a black `SolidBackground` leaves only emissive materials lighting the scene
+    pub fn with_background(mut self, background: Arc<dyn Background>) -> Self
+    {
+        self._background = background;
+        self
+    }
+
+    // Objects to aim shadow rays at. They must also be part of the world and
+    // implement `pdf_value` and `random`, which every finite primitive does.
+    pub fn with_lights(mut self, lights: HittableList) -> Self
+    {
+        self._lights = lights;
+        self
+    }
+
+    // Hands the final render settings to the camera
+    pub fn with_settings(mut self, settings: &RenderSettings) -> Self
+    {
+        self._camera = self._camera.with_settings(settings);
+        self
+    }
+
+    pub fn background(&self) -> &dyn Background
+    {
+        self._background.as_ref()
+    }
+
+    pub fn camera(&self) -> &Camera
+    {
+        &self._camera
+    }
+
+    pub fn world(&self) -> &HittableList
+    {
+        &self._world
+    }
+
+    pub fn lights(&self) -> Option<&dyn Hittable>
+    {
+        if self._lights.objects.is_empty() { None } else { Some(&self._lights) }
+    }
+
+    pub fn view(&self) -> SceneView<'_>
+    {
+        SceneView { world: &self._world, lights: self.lights(), background: self.background() }
+    }
+
+    pub fn render_pixel(&self, w: u32, h: u32, u: u32, v: u32) -> Vector4<f32>
+    {
+        self._camera.render(w, h, u, v, self.view())
+    }
+
+    // Unresolved sum of `count` samples, see `Camera::sample_pixel`
+    pub fn sample_pixel(&self, w: u32, h: u32, u: u32, v: u32, first_sample: u32, count: u32) -> Vector4<f32>
+    {
+        self._camera.sample_pixel(w, h, u, v, self.view(), first_sample..first_sample + count)
+    }
+}
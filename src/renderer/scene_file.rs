@@ -0,0 +1,891 @@
+// Loader for the plain-text scene description format.
+//
+// One directive per line, `#` starts a comment. A directive is a keyword,
+// optional positional words and `key=value` fields; vectors are written as
+// comma separated numbers without spaces:
+//
+//     render width=1280 height=720 spp=64 depth=50 threads=8 seed=1 output=out.png
+//     render rr_depth=5                       # bounces before Russian roulette may end a path
+//     render tile_size=16 tile_order=hilbert  # also scanline and spiral, the default
+//     render spectral=true                    # trace wavelengths instead of RGB, for dispersion
+//     camera lookfrom=-2,2,1 lookat=0,0,-1 fov=20
+//     camera lookfrom=0,1,3 lookat=0,0,0 vup=0,1,0 aperture=2 focus=auto   # aperture in degrees, or focus=3.2
+//     camera lookfrom=0,0,5 lookat=0,0,0 shutter_open=0 shutter_close=1    # motion blur
+//     material ground lambertian albedo=0.8,0.8,0.0
+//     material glass dielectric ior=1.5
+//     material prism dielectric glass=sf11     # dispersive: bk7, fused_silica, sf11 or diamond
+//     material lens dielectric cauchy=1.5046,0.0042   # n = a + b / lambda^2, lambda in micrometres
+//     material wine dielectric ior=1.35 tint=0.5,0.05,0.1 tint_distance=0.2   # colour after 0.2 units inside
+//     material ink dielectric ior=1.33 absorption=4,2,0.5   # or the absorption coefficient directly
+//     material bubble dielectric ior=1 priority=1   # where dielectrics overlap the higher priority (default 0) wins
+//     material gold metal albedo=0.8,0.6,0.2 fuzz=0.0
+//     material lamp diffuse_light emit=4,4,4
+//     material brushed conductor metal=aluminium roughness=0.3 anisotropy=0.6   # gold, copper or aluminium
+//     material custom conductor eta=0.2,0.9,1.1 k=3.9,2.5,2.1 roughness=0.1     # complex IOR per channel
+//     material frosted rough_dielectric ior=1.5 roughness=0.2   # also absorption, tint and priority
+//     material paint principled base_color=0.8,0.1,0.1 roughness=0.4 clearcoat=1 clearcoat_gloss=0.9
+//         # also metallic, anisotropy, specular, specular_tint, sheen, sheen_tint, transmission and priority
+//     texture floor checker scale=0.32 even=0.2,0.3,0.1 odd=0.9,0.9,0.9
+//     texture earth image file=earthmap.jpg
+//     texture marble noise scale=4
+//     material checkered lambertian texture=floor  # instead of albedo=
+//     background color=0,0,0                  # default is the sky gradient
+//     background gradient bottom=1,1,1 top=0.5,0.7,1
+//     background envmap file=sky.hdr rotation=90 intensity=1.5   # .hdr and .exr are linear, other images sRGB
+//     sphere center=0,-100.5,-1 radius=100 material=ground
+//     sphere center=0,0,-1 center1=0.5,0,-1 radius=0.5 material=gold     # at center1 at time 1
+//     triangle v0=-1,0,-2 v1=1,0,-2 v2=0,1,-2 material=gold
+//     quad q=-1,0,-2 u=2,0,0 v=0,1,0 material=lamp      # parallelogram with corner q and edges u, v
+//     box min=-1,0,-1 max=1,2,1 material=ground
+//     box center=0,1,0 a=2,0,0 b=0,2,0 c=0,0,2 material=ground   # oriented by its three edges
+//     plane point=0,0,0 normal=0,1,0 material=ground   # infinite
+//     disk center=0,2,0 normal=0,-1,0 radius=0.5 material=lamp
+//     cylinder base=0,0,0 top=0,1,0 radius=0.3 material=gold
+//     cone base=0,0,0 apex=0,1,0 radius=0.3 material=gold
+//     medium sphere center=0,1,0 radius=1 density=0.5 albedo=1,1,1   # fog inside a sphere
+//     medium box min=-5,0,-5 max=5,3,5 density=0.02 albedo=0.8,0.8,0.9
+//     mesh file=models/teapot.obj             # materials from the MTL file, texture maps are ignored
+//     mesh file=models/teapot.obj material=gold   # the MTL file is not read
+//     mesh file=models/teapot.obj move=1,0,0  # slides by move over the shutter interval
+//     mesh name=teapot file=models/teapot.obj # not placed, only defines geometry for instances
+//     instance teapot translate=2,0,-1 rotate=0,45,0 scale=0.5   # rotate in degrees around x, y, z
+//
+// Relative file paths are resolved against the directory of the scene file.
+// Objects with a `diffuse_light` material are also sampled directly as lights,
+// except for moving meshes and instances, which are only found by chance.
+// Every field of `render` is optional and command-line flags take precedence.
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+use cgmath::{Array, Vector3};
+use crate::renderer::animated::AnimatedTranslate;
+use crate::renderer::background::{Background, EnvironmentMap, GradientBackground, SolidBackground};
+use crate::renderer::bvh::BvhNode;
+use crate::renderer::camera::Camera;
+use crate::renderer::cylinder::{Cone, Cylinder};
+use crate::renderer::hittable::Hittable;
+use crate::renderer::hittable_list::HittableList;
+use crate::renderer::material::{absorption_from_tint, Conductor, Dielectric, DiffuseLight, Isotropic, Lambertian, Material, Metal, RoughDielectric};
+use crate::renderer::medium::ConstantMedium;
+use crate::renderer::obj_loader::load_obj;
+use crate::renderer::plane::{Disk, Plane};
+use crate::renderer::principled::Principled;
+use crate::renderer::quad::{Cuboid, Quad};
+use crate::renderer::scene::Scene;
+use crate::renderer::settings::RenderSettings;
+use crate::renderer::spectrum::Dispersion;
+use crate::renderer::sphere::Sphere;
+use crate::renderer::texture::{CheckerTexture, ImageTexture, NoiseTexture, SolidColor, Texture};
+use crate::renderer::tile_scheduler::TileOrder;
+use crate::renderer::transform::Transform;
+use crate::renderer::triangle::Triangle;
+
+#[derive(Debug)]
+pub struct SceneError
+{
+    // 1-based line of the offending directive, `None` for whole-file problems
+    pub line: Option<usize>,
+    pub message: String
+}
+
+impl SceneError
+{
+    fn new(line: Option<usize>, message: String) -> Self
+    {
+        SceneError { line, message }
+    }
+}
+
+impl fmt::Display for SceneError
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        match self.line
+        {
+            Some(line) => write!(f, "line {}: {}", line, self.message),
+            None => write!(f, "{}", self.message)
+        }
+    }
+}
+
+impl Error for SceneError {}
+
+struct Directive<'a>
+{
+    line: usize,
+    keyword: &'a str,
+    words: Vec<&'a str>,
+    fields: Vec<(&'a str, &'a str)>
+}
+
+impl<'a> Directive<'a>
+{
+    fn parse(line: usize, text: &'a str) -> Option<Self>
+    {
+        let text = match text.find('#')
+        {
+            Some(i) => &text[..i],
+            None => text
+        };
+
+        let mut tokens = text.split_whitespace();
+        let keyword = tokens.next()?;
+
+        let mut words = vec![];
+        let mut fields = vec![];
+        for token in tokens
+        {
+            match token.find('=')
+            {
+                Some(i) => fields.push((&token[..i], &token[i + 1..])),
+                None => words.push(token)
+            }
+        }
+
+        Some(Directive { line, keyword, words, fields })
+    }
+
+    fn error(&self, message: String) -> SceneError
+    {
+        SceneError::new(Some(self.line), message)
+    }
+
+    // Rejects fields the directive does not understand, catches typos early
+    fn allow_fields(&self, allowed: &[&str]) -> Result<(), SceneError>
+    {
+        for (key, _) in &self.fields
+        {
+            if !allowed.contains(key)
+            {
+                return Err(self.error(format!("unknown field '{}' for {} (expected one of: {})", key, self.keyword, allowed.join(", "))));
+            }
+        }
+        Ok(())
+    }
+
+    fn word(&self, index: usize, what: &str) -> Result<&'a str, SceneError>
+    {
+        self.words.get(index).copied().ok_or_else(|| self.error(format!("{} is missing its {}", self.keyword, what)))
+    }
+
+    fn field(&self, key: &str) -> Option<&'a str>
+    {
+        self.fields.iter().rev().find(|(k, _)| *k == key).map(|(_, v)| *v)
+    }
+
+    fn required(&self, key: &str) -> Result<&'a str, SceneError>
+    {
+        self.field(key).ok_or_else(|| self.error(format!("{} is missing required field '{}'", self.keyword, key)))
+    }
+
+    fn parse_f32(&self, key: &str, value: &str) -> Result<f32, SceneError>
+    {
+        value.parse::<f32>().map_err(|_| self.error(format!("field '{}': expected a number, got '{}'", key, value)))
+    }
+
+    fn parse_vec3(&self, key: &str, value: &str) -> Result<Vector3<f32>, SceneError>
+    {
+        let parts: Vec<&str> = value.split(',').collect();
+        if parts.len() != 3
+        {
+            return Err(self.error(format!("field '{}': expected three comma separated numbers, got '{}'", key, value)));
+        }
+        Ok(Vector3::new(self.parse_f32(key, parts[0])?, self.parse_f32(key, parts[1])?, self.parse_f32(key, parts[2])?))
+    }
+
+    fn f32(&self, key: &str) -> Result<f32, SceneError>
+    {
+        self.parse_f32(key, self.required(key)?)
+    }
+
+    fn f32_or(&self, key: &str, default: f32) -> Result<f32, SceneError>
+    {
+        match self.field(key)
+        {
+            Some(value) => self.parse_f32(key, value),
+            None => Ok(default)
+        }
+    }
+
+    fn int_opt<T: FromStr>(&self, key: &str) -> Result<Option<T>, SceneError>
+    {
+        match self.field(key)
+        {
+            Some(value) => value.parse::<T>().map(Some).map_err(|_| self.error(format!("field '{}': expected a non-negative integer, got '{}'", key, value))),
+            None => Ok(None)
+        }
+    }
+
+    fn positive_opt<T: FromStr + PartialOrd + Default>(&self, key: &str) -> Result<Option<T>, SceneError>
+    {
+        match self.int_opt::<T>(key)?
+        {
+            Some(n) if n <= T::default() => Err(self.error(format!("field '{}' must be greater than zero", key))),
+            n => Ok(n)
+        }
+    }
+
+    fn vec3(&self, key: &str) -> Result<Vector3<f32>, SceneError>
+    {
+        self.parse_vec3(key, self.required(key)?)
+    }
+
+    fn vec3_opt(&self, key: &str) -> Result<Option<Vector3<f32>>, SceneError>
+    {
+        match self.field(key)
+        {
+            Some(value) => Ok(Some(self.parse_vec3(key, value)?)),
+            None => Ok(None)
+        }
+    }
+
+    // Beer-Lambert coefficient of a dielectric's interior, from `absorption=` or `tint=`
+    fn absorption(&self) -> Result<Vector3<f32>, SceneError>
+    {
+        if self.field("tint_distance").is_some() && self.field("tint").is_none()
+        {
+            return Err(self.error("'tint_distance' needs a 'tint'".to_string()));
+        }
+        match (self.vec3_opt("absorption")?, self.vec3_opt("tint")?)
+        {
+            (Some(_), Some(_)) => Err(self.error("give either 'absorption' or 'tint', not both".to_string())),
+            (Some(absorption), None) => Ok(absorption),
+            (None, Some(tint)) => Ok(absorption_from_tint(tint, self.f32_or("tint_distance", 1.0)?)),
+            (None, None) => Ok(Vector3::new(0.0, 0.0, 0.0))
+        }
+    }
+}
+
+struct SceneBuilder
+{
+    base_dir: PathBuf,
+    camera: Option<Camera>,
+    background: Option<Arc<dyn Background>>,
+    textures: HashMap<String, Arc<dyn Texture>>,
+    materials: HashMap<String, Arc<dyn Material>>,
+    // Named geometry that is only placed through `instance`
+    prototypes: HashMap<String, Arc<dyn Hittable>>,
+    objects: HittableList,
+    // Infinite planes, kept out of the BVH
+    unbounded: HittableList,
+    // Emissive objects, also sampled directly
+    lights: HittableList
+}
+
+impl SceneBuilder
+{
+    fn new(base_dir: &Path) -> Self
+    {
+        SceneBuilder
+        {
+            base_dir: base_dir.to_path_buf(),
+            camera: None,
+            background: None,
+            textures: HashMap::new(),
+            materials: HashMap::new(),
+            prototypes: HashMap::new(),
+            objects: HittableList::new(),
+            unbounded: HittableList::new(),
+            lights: HittableList::new()
+        }
+    }
+
+    // Adds a shape that can be light-sampled, registering it as a light if it emits
+    fn add_sampled(&mut self, object: Arc<dyn Hittable>, material: &Arc<dyn Material>)
+    {
+        if material.is_emissive()
+        {
+            self.lights.add(object.clone());
+        }
+        self.objects.add(object);
+    }
+
+    fn material(&self, d: &Directive) -> Result<Arc<dyn Material>, SceneError>
+    {
+        let name = d.required("material")?;
+        self.materials.get(name).cloned().ok_or_else(|| d.error(format!("unknown material '{}'", name)))
+    }
+
+    // A material colour given either inline as `color_key=r,g,b` or by name as `texture=NAME`
+    fn texture(&self, d: &Directive, color_key: &str) -> Result<Arc<dyn Texture>, SceneError>
+    {
+        match (d.field(color_key), d.field("texture"))
+        {
+            (Some(_), Some(_)) => Err(d.error(format!("{} takes either '{}' or 'texture', not both", d.keyword, color_key))),
+            (None, Some(name)) => self.textures.get(name).cloned().ok_or_else(|| d.error(format!("unknown texture '{}'", name))),
+            _ => Ok(Arc::new(SolidColor::new(d.vec3(color_key)?)))
+        }
+    }
+
+    fn render(&mut self, d: &Directive, settings: &mut RenderSettings) -> Result<(), SceneError>
+    {
+        d.allow_fields(&["width", "height", "spp", "depth", "rr_depth", "threads", "tile_size", "tile_order", "seed", "spectral", "output"])?;
+
+        if let Some(width) = d.positive_opt("width")? { settings.width = width; }
+        if let Some(height) = d.positive_opt("height")? { settings.height = height; }
+        if let Some(spp) = d.positive_opt("spp")? { settings.samples_per_pixel = spp; }
+        if let Some(depth) = d.positive_opt("depth")? { settings.max_depth = depth; }
+        if let Some(depth) = d.int_opt("rr_depth")? { settings.roulette_depth = depth; }
+        if let Some(threads) = d.positive_opt("threads")? { settings.threads = threads; }
+        if let Some(size) = d.positive_opt("tile_size")? { settings.tile_size = size; }
+        if let Some(order) = d.field("tile_order")
+        {
+            settings.tile_order = TileOrder::from_name(order).ok_or_else(|| d.error(format!("field 'tile_order': expected scanline, spiral or hilbert, got '{}'", order)))?;
+        }
+        if let Some(seed) = d.int_opt("seed")? { settings.seed = seed; }
+        if let Some(spectral) = d.field("spectral")
+        {
+            settings.spectral = spectral.parse().map_err(|_| d.error(format!("field 'spectral': expected true or false, got '{}'", spectral)))?;
+        }
+        if let Some(output) = d.field("output") { settings.output = self.base_dir.join(output); }
+        Ok(())
+    }
+
+    fn camera(&mut self, d: &Directive) -> Result<(), SceneError>
+    {
+        d.allow_fields(&["lookfrom", "lookat", "vup", "fov", "aspect", "aperture", "focus", "shutter_open", "shutter_close"])?;
+        if self.camera.is_some()
+        {
+            return Err(d.error("camera is defined more than once".to_string()));
+        }
+
+        let mut builder = Camera::builder(d.vec3("lookfrom")?, d.vec3("lookat")?)
+            .vfov(d.f32_or("fov", 90.0)?)
+            .defocus_angle(d.f32_or("aperture", 0.0)?)
+            .shutter(d.f32_or("shutter_open", 0.0)?, d.f32_or("shutter_close", 0.0)?);
+        if let Some(vup) = d.vec3_opt("vup")?
+        {
+            builder = builder.vup(vup);
+        }
+        if let Some(aspect) = d.field("aspect")
+        {
+            builder = builder.aspect_ratio(d.parse_f32("aspect", aspect)?);
+        }
+        match d.field("focus")
+        {
+            Some("auto") => builder = builder.auto_focus(),
+            Some(value) => builder = builder.focus_dist(d.parse_f32("focus", value)?),
+            None => {}
+        }
+
+        self.camera = Some(builder.build());
+        Ok(())
+    }
+
+    fn background(&mut self, d: &Directive) -> Result<(), SceneError>
+    {
+        let background: Arc<dyn Background> = match d.words.first().copied().unwrap_or("solid")
+        {
+            "solid" =>
+            {
+                d.allow_fields(&["color"])?;
+                Arc::new(SolidBackground::new(d.vec3("color")?))
+            }
+            "gradient" =>
+            {
+                d.allow_fields(&["bottom", "top"])?;
+                Arc::new(GradientBackground::new(d.vec3("bottom")?, d.vec3("top")?))
+            }
+            "envmap" =>
+            {
+                d.allow_fields(&["file", "rotation", "intensity"])?;
+                let path = self.base_dir.join(d.required("file")?);
+                let envmap = EnvironmentMap::load(&path, d.f32_or("rotation", 0.0)?, d.f32_or("intensity", 1.0)?).map_err(|err| d.error(err))?;
+                Arc::new(envmap)
+            }
+            kind => return Err(d.error(format!("unknown background type '{}'", kind)))
+        };
+
+        if self.background.is_some()
+        {
+            return Err(d.error("background is defined more than once".to_string()));
+        }
+        self.background = Some(background);
+        Ok(())
+    }
+
+    fn add_texture(&mut self, d: &Directive) -> Result<(), SceneError>
+    {
+        let name = d.word(0, "name")?;
+        let kind = d.word(1, "type")?;
+
+        let texture: Arc<dyn Texture> = match kind
+        {
+            "solid" =>
+            {
+                d.allow_fields(&["color"])?;
+                Arc::new(SolidColor::new(d.vec3("color")?))
+            }
+            "checker" =>
+            {
+                d.allow_fields(&["scale", "even", "odd"])?;
+                let scale = d.f32_or("scale", 1.0)?;
+                if scale <= 0.0
+                {
+                    return Err(d.error("field 'scale': must be positive".to_string()));
+                }
+                Arc::new(CheckerTexture::from_colors(scale, d.vec3("even")?, d.vec3("odd")?))
+            }
+            "image" =>
+            {
+                d.allow_fields(&["file"])?;
+                let path = self.base_dir.join(d.required("file")?);
+                Arc::new(ImageTexture::load(&path).map_err(|err| d.error(err))?)
+            }
+            "noise" =>
+            {
+                d.allow_fields(&["scale"])?;
+                Arc::new(NoiseTexture::new(d.f32_or("scale", 1.0)?))
+            }
+            _ => return Err(d.error(format!("unknown texture type '{}'", kind)))
+        };
+
+        if self.textures.insert(name.to_string(), texture).is_some()
+        {
+            return Err(d.error(format!("texture '{}' is defined more than once", name)));
+        }
+        Ok(())
+    }
+
+    fn add_material(&mut self, d: &Directive) -> Result<(), SceneError>
+    {
+        let name = d.word(0, "name")?;
+        let kind = d.word(1, "type")?;
+
+        let material: Arc<dyn Material> = match kind
+        {
+            "lambertian" =>
+            {
+                d.allow_fields(&["albedo", "texture"])?;
+                Arc::new(Lambertian::from_texture(self.texture(d, "albedo")?))
+            }
+            "metal" =>
+            {
+                d.allow_fields(&["albedo", "texture", "fuzz"])?;
+                Arc::new(Metal::from_texture(self.texture(d, "albedo")?, d.f32_or("fuzz", 0.0)?))
+            }
+            "dielectric" =>
+            {
+                d.allow_fields(&["ior", "glass", "cauchy", "absorption", "tint", "tint_distance", "priority"])?;
+                if d.field("ior").is_some() && (d.field("glass").is_some() || d.field("cauchy").is_some())
+                {
+                    return Err(d.error("dielectric takes 'ior' or a dispersive 'glass' or 'cauchy', not both".to_string()));
+                }
+                let dielectric = match (d.field("glass"), d.field("cauchy"))
+                {
+                    (Some(_), Some(_)) => return Err(d.error("dielectric takes either 'glass' or 'cauchy', not both".to_string())),
+                    (Some(glass), None) => Dielectric::glass(glass)
+                        .ok_or_else(|| d.error(format!("unknown glass '{}', expected bk7, fused_silica, sf11 or diamond", glass)))?,
+                    (None, Some(value)) =>
+                    {
+                        let parts: Vec<&str> = value.split(',').collect();
+                        if parts.len() != 2
+                        {
+                            return Err(d.error(format!("field 'cauchy': expected two comma separated numbers, got '{}'", value)));
+                        }
+                        Dielectric::dispersive(Dispersion::Cauchy(d.parse_f32("cauchy", parts[0])?, d.parse_f32("cauchy", parts[1])?))
+                    }
+                    (None, None) => Dielectric::new(d.f32("ior")?)
+                };
+                Arc::new(dielectric.with_priority(d.int_opt("priority")?.unwrap_or(0)).absorbing(d.absorption()?))
+            }
+            "conductor" =>
+            {
+                d.allow_fields(&["metal", "eta", "k", "roughness", "anisotropy"])?;
+                let (roughness, anisotropy) = (d.f32_or("roughness", 0.0)?, d.f32_or("anisotropy", 0.0)?);
+                match d.field("metal")
+                {
+                    Some(metal) => Arc::new(Conductor::preset(metal, roughness, anisotropy)
+                        .ok_or_else(|| d.error(format!("unknown metal '{}', expected gold, copper or aluminium", metal)))?),
+                    None => Arc::new(Conductor::new(d.vec3("eta")?, d.vec3("k")?, roughness, anisotropy))
+                }
+            }
+            "principled" =>
+            {
+                d.allow_fields(&["base_color", "texture", "metallic", "roughness", "anisotropy", "specular", "specular_tint",
+                                 "sheen", "sheen_tint", "clearcoat", "clearcoat_gloss", "transmission", "priority"])?;
+                type Setter = fn(Principled, f32) -> Principled;
+                let setters: [(&str, Setter); 10] = [
+                    ("metallic", Principled::metallic), ("roughness", Principled::roughness), ("anisotropy", Principled::anisotropy),
+                    ("specular", Principled::specular), ("specular_tint", Principled::specular_tint), ("sheen", Principled::sheen),
+                    ("sheen_tint", Principled::sheen_tint), ("clearcoat", Principled::clearcoat),
+                    ("clearcoat_gloss", Principled::clearcoat_gloss), ("transmission", Principled::transmission)];
+
+                // Parameters left out keep their defaults
+                let mut principled = Principled::from_texture(self.texture(d, "base_color")?);
+                for &(key, set) in &setters
+                {
+                    if let Some(value) = d.field(key)
+                    {
+                        principled = set(principled, d.parse_f32(key, value)?);
+                    }
+                }
+                Arc::new(principled.priority(d.int_opt("priority")?.unwrap_or(0)))
+            }
+            "rough_dielectric" =>
+            {
+                d.allow_fields(&["ior", "roughness", "anisotropy", "absorption", "tint", "tint_distance", "priority"])?;
+                Arc::new(RoughDielectric::new(d.f32("ior")?, d.f32_or("roughness", 0.0)?, d.f32_or("anisotropy", 0.0)?)
+                    .with_priority(d.int_opt("priority")?.unwrap_or(0))
+                    .absorbing(d.absorption()?))
+            }
+            "diffuse_light" =>
+            {
+                d.allow_fields(&["emit", "texture"])?;
+                Arc::new(DiffuseLight::from_texture(self.texture(d, "emit")?))
+            }
+            _ => return Err(d.error(format!("unknown material type '{}'", kind)))
+        };
+
+        if self.materials.insert(name.to_string(), material).is_some()
+        {
+            return Err(d.error(format!("material '{}' is defined more than once", name)));
+        }
+        Ok(())
+    }
+
+    fn add_sphere(&mut self, d: &Directive) -> Result<(), SceneError>
+    {
+        d.allow_fields(&["center", "center1", "radius", "material"])?;
+        let material = self.material(d)?;
+        let sphere = match d.vec3_opt("center1")?
+        {
+            Some(center1) => Sphere::moving(d.vec3("center")?, center1, d.f32("radius")?, material.clone()),
+            None => Sphere::new(d.vec3("center")?, d.f32("radius")?, material.clone())
+        };
+        self.add_sampled(Arc::new(sphere), &material);
+        Ok(())
+    }
+
+    fn add_triangle(&mut self, d: &Directive) -> Result<(), SceneError>
+    {
+        d.allow_fields(&["v0", "v1", "v2", "n0", "n1", "n2", "material"])?;
+        let (p0, p1, p2) = (d.vec3("v0")?, d.vec3("v1")?, d.vec3("v2")?);
+        let material = self.material(d)?;
+
+        let triangle = match (d.vec3_opt("n0")?, d.vec3_opt("n1")?, d.vec3_opt("n2")?)
+        {
+            (Some(n0), Some(n1), Some(n2)) => Triangle::with_normals(p0, p1, p2, [n0, n1, n2], material.clone()),
+            (None, None, None) => Triangle::new(p0, p1, p2, material.clone()),
+            _ => return Err(d.error("triangle needs either all of n0, n1, n2 or none of them".to_string()))
+        };
+        self.add_sampled(Arc::new(triangle), &material);
+        Ok(())
+    }
+
+    fn add_quad(&mut self, d: &Directive) -> Result<(), SceneError>
+    {
+        d.allow_fields(&["q", "u", "v", "material"])?;
+        let material = self.material(d)?;
+        let quad = Quad::new(d.vec3("q")?, d.vec3("u")?, d.vec3("v")?, material.clone());
+        self.add_sampled(Arc::new(quad), &material);
+        Ok(())
+    }
+
+    fn add_box(&mut self, d: &Directive) -> Result<(), SceneError>
+    {
+        d.allow_fields(&["min", "max", "center", "a", "b", "c", "material"])?;
+        let material = self.material(d)?;
+        let cuboid = if d.field("center").is_some()
+        {
+            Cuboid::oriented(d.vec3("center")?, [d.vec3("a")?, d.vec3("b")?, d.vec3("c")?], material.clone())
+        }
+        else
+        {
+            Cuboid::new(d.vec3("min")?, d.vec3("max")?, material.clone())
+        };
+        self.add_sampled(Arc::new(cuboid), &material);
+        Ok(())
+    }
+
+    fn add_plane(&mut self, d: &Directive) -> Result<(), SceneError>
+    {
+        d.allow_fields(&["point", "normal", "material"])?;
+        let plane = Plane::new(d.vec3("point")?, d.vec3("normal")?, self.material(d)?);
+        self.unbounded.add(Arc::new(plane));
+        Ok(())
+    }
+
+    fn add_disk(&mut self, d: &Directive) -> Result<(), SceneError>
+    {
+        d.allow_fields(&["center", "normal", "radius", "material"])?;
+        let material = self.material(d)?;
+        let disk = Disk::new(d.vec3("center")?, d.vec3("normal")?, d.f32("radius")?, material.clone());
+        self.add_sampled(Arc::new(disk), &material);
+        Ok(())
+    }
+
+    fn add_cylinder(&mut self, d: &Directive) -> Result<(), SceneError>
+    {
+        d.allow_fields(&["base", "top", "radius", "material"])?;
+        let material = self.material(d)?;
+        let cylinder = Cylinder::new(d.vec3("base")?, d.vec3("top")?, d.f32("radius")?, material.clone());
+        self.add_sampled(Arc::new(cylinder), &material);
+        Ok(())
+    }
+
+    fn add_cone(&mut self, d: &Directive) -> Result<(), SceneError>
+    {
+        d.allow_fields(&["base", "apex", "radius", "material"])?;
+        let material = self.material(d)?;
+        let cone = Cone::new(d.vec3("base")?, d.vec3("apex")?, d.f32("radius")?, material.clone());
+        self.add_sampled(Arc::new(cone), &material);
+        Ok(())
+    }
+
+    fn add_medium(&mut self, d: &Directive) -> Result<(), SceneError>
+    {
+        let kind = d.word(0, "boundary shape")?;
+        // The boundary only delimits the volume, its material is never used
+        let unused: Arc<dyn Material> = Arc::new(Lambertian::new(Vector3::from_value(0.0)));
+        let boundary: Arc<dyn Hittable> = match kind
+        {
+            "sphere" =>
+            {
+                d.allow_fields(&["center", "radius", "density", "albedo", "texture"])?;
+                Arc::new(Sphere::new(d.vec3("center")?, d.f32("radius")?, unused))
+            }
+            "box" =>
+            {
+                d.allow_fields(&["min", "max", "density", "albedo", "texture"])?;
+                Arc::new(Cuboid::new(d.vec3("min")?, d.vec3("max")?, unused))
+            }
+            _ => return Err(d.error(format!("unknown medium boundary '{}' (expected sphere or box)", kind)))
+        };
+
+        let density = d.f32("density")?;
+        if density <= 0.0
+        {
+            return Err(d.error("field 'density' must be greater than zero".to_string()));
+        }
+        let phase_function = Arc::new(Isotropic::from_texture(self.texture(d, "albedo")?));
+        self.objects.add(Arc::new(ConstantMedium::new(boundary, density, phase_function)));
+        Ok(())
+    }
+
+    fn add_mesh(&mut self, d: &Directive) -> Result<(), SceneError>
+    {
+        d.allow_fields(&["name", "file", "material", "move"])?;
+        let path = self.base_dir.join(d.required("file")?);
+        let material_override = match d.field("material")
+        {
+            Some(_) => Some(self.material(d)?),
+            None => None
+        };
+
+        let motion = d.vec3_opt("move")?;
+
+        let meshes = load_obj(&path, material_override).map_err(|err| d.error(err))?;
+        let mut placed: Vec<(Arc<dyn Hittable>, Arc<dyn Material>)> = vec![];
+        for mesh in meshes
+        {
+            let material = mesh.material().clone();
+            match motion
+            {
+                Some(motion) => placed.push((Arc::new(AnimatedTranslate::new(Arc::new(mesh), Vector3::from_value(0.0), motion)), material)),
+                None => placed.push((Arc::new(mesh), material))
+            }
+        }
+
+        match d.field("name")
+        {
+            Some(name) =>
+            {
+                if placed.is_empty()
+                {
+                    return Err(d.error(format!("{} has no geometry", path.display())));
+                }
+                let mut objects: Vec<Arc<dyn Hittable>> = placed.into_iter().map(|(mesh, _)| mesh).collect();
+                let geometry: Arc<dyn Hittable> = Arc::new(BvhNode::from_objects(&mut objects));
+                if self.prototypes.insert(name.to_string(), geometry).is_some()
+                {
+                    return Err(d.error(format!("mesh '{}' is defined more than once", name)));
+                }
+            }
+            None =>
+            {
+                for (mesh, material) in placed
+                {
+                    // Moving meshes can't be sampled as lights
+                    if motion.is_some() { self.objects.add(mesh) } else { self.add_sampled(mesh, &material) }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn add_instance(&mut self, d: &Directive) -> Result<(), SceneError>
+    {
+        d.allow_fields(&["translate", "rotate", "scale"])?;
+        let name = d.word(0, "mesh name")?;
+        let geometry = self.prototypes.get(name).cloned().ok_or_else(|| d.error(format!("unknown mesh '{}'", name)))?;
+
+        let translate = d.vec3_opt("translate")?.unwrap_or_else(|| Vector3::from_value(0.0));
+        let rotate = d.vec3_opt("rotate")?.unwrap_or_else(|| Vector3::from_value(0.0));
+        // A single number scales uniformly
+        let scale = match d.field("scale")
+        {
+            Some(value) if value.contains(',') => d.parse_vec3("scale", value)?,
+            Some(value) => Vector3::from_value(d.parse_f32("scale", value)?),
+            None => Vector3::from_value(1.0)
+        };
+
+        let instance = Transform::from_parts(geometry, translate, rotate, scale).ok_or_else(|| d.error("instance scale must not be zero".to_string()))?;
+        self.objects.add(Arc::new(instance));
+        Ok(())
+    }
+}
+
+// Builds the scene and applies its `render` directive to `settings`
+pub fn parse_scene(source: &str, base_dir: &Path, settings: &mut RenderSettings) -> Result<Scene, SceneError>
+{
+    let mut builder = SceneBuilder::new(base_dir);
+
+    for (index, text) in source.lines().enumerate()
+    {
+        let d = match Directive::parse(index + 1, text)
+        {
+            Some(d) => d,
+            None => continue
+        };
+
+        match d.keyword
+        {
+            "render" => builder.render(&d, settings)?,
+            "camera" => builder.camera(&d)?,
+            "background" => builder.background(&d)?,
+            "texture" => builder.add_texture(&d)?,
+            "material" => builder.add_material(&d)?,
+            "sphere" => builder.add_sphere(&d)?,
+            "triangle" => builder.add_triangle(&d)?,
+            "quad" => builder.add_quad(&d)?,
+            "box" => builder.add_box(&d)?,
+            "plane" => builder.add_plane(&d)?,
+            "disk" => builder.add_disk(&d)?,
+            "cylinder" => builder.add_cylinder(&d)?,
+            "cone" => builder.add_cone(&d)?,
+            "medium" => builder.add_medium(&d)?,
+            "mesh" => builder.add_mesh(&d)?,
+            "instance" => builder.add_instance(&d)?,
+            _ => return Err(d.error(format!("unknown directive '{}'", d.keyword)))
+        }
+    }
+
+    let camera = builder.camera.take().ok_or_else(|| SceneError::new(None, "scene has no camera".to_string()))?;
+
+    let mut world = HittableList::new();
+    if !builder.objects.objects.is_empty()
+    {
+        world.add(Arc::new(BvhNode::new(builder.objects.clone())));
+    }
+    for plane in &builder.unbounded.objects
+    {
+        world.add(plane.clone());
+    }
+
+    let scene = Scene::new(camera.with_settings(settings), world).with_lights(builder.lights.clone());
+    Ok(match builder.background
+    {
+        Some(background) => scene.with_background(background),
+        None => scene
+    })
+}
+
+pub fn load_scene(path: &Path, settings: &mut RenderSettings) -> Result<Scene, SceneError>
+{
+    let source = fs::read_to_string(path).map_err(|err| SceneError::new(None, format!("failed to read file: {}", err)))?;
+
+    parse_scene(&source, path.parent().unwrap_or_else(|| Path::new(".")), settings)
+}
+
+#[cfg(test)]
+mod test
+{
+    use super::*;
+
+    #[test]
+    fn test_parse_scene()
+    {
+        let source = "
+            # three spheres
+            camera lookfrom=0,0,1 lookat=0,0,-1 fov=45
+            material ground lambertian albedo=0.8,0.8,0.0
+            material glass dielectric ior=1.5
+            sphere center=0,-100.5,-1 radius=100 material=ground
+            sphere center=0,0,-1 radius=0.5 material=glass   # trailing comment
+        ";
+        let scene = parse_scene(source, Path::new("."), &mut RenderSettings::default()).unwrap();
+        assert_eq!(scene.camera().fov(), 45.0);
+        assert_eq!(scene.world().objects.len(), 1);
+        assert!(scene.lights().is_none());
+
+        let source = "
+            camera lookfrom=0,0,1 lookat=0,0,-1
+            material lamp diffuse_light emit=4,4,4
+            quad q=-1,2,-2 u=2,0,0 v=0,0,2 material=lamp
+            cylinder base=0,0,-1 top=0,1,-1 radius=0.5 material=lamp
+        ";
+        let scene = parse_scene(source, Path::new("."), &mut RenderSettings::default()).unwrap();
+        assert!(scene.lights().unwrap().pdf_value(Vector3::new(0.0, 0.0, -1.0), Vector3::new(0.0, 1.0, 0.0)) > 0.0);
+    }
+
+    #[test]
+    fn test_render_directive()
+    {
+        let source = "
+            render width=320 height=240 spp=8 seed=3 tile_order=scanline spectral=true output=out.png
+            camera lookfrom=0,0,1 lookat=0,0,-1
+        ";
+        let mut settings = RenderSettings::default();
+        let scene = parse_scene(source, Path::new("scenes"), &mut settings).unwrap();
+        assert_eq!((settings.width, settings.height, settings.samples_per_pixel, settings.seed), (320, 240, 8, 3));
+        assert_eq!(settings.output, Path::new("scenes").join("out.png"));
+        assert_eq!(settings.tile_order, TileOrder::Scanline);
+        assert_eq!(scene.camera().width(), 320);
+        assert_eq!(scene.camera().samples_per_pixel(), 8);
+        assert!(scene.camera().spectral());
+
+        let err = parse_scene("render spp=0", Path::new("."), &mut RenderSettings::default()).err().unwrap();
+        assert!(err.message.contains("'spp' must be greater than zero"));
+    }
+
+    #[test]
+    fn test_parse_errors_point_at_line()
+    {
+        let err = parse_scene("camera lookfrom=0,0,1 lookat=0,0,-1\nsphere center=0,0,-1 radius=0.5 material=missing", Path::new("."), &mut RenderSettings::default()).err().unwrap();
+        assert_eq!(err.line, Some(2));
+        assert!(err.message.contains("unknown material 'missing'"));
+
+        let err = parse_scene("camera lookfrom=0,0 lookat=0,0,-1", Path::new("."), &mut RenderSettings::default()).err().unwrap();
+        assert_eq!(err.line, Some(1));
+        assert!(err.message.contains("lookfrom"));
+
+        let err = parse_scene("material red lambertian colour=1,0,0", Path::new("."), &mut RenderSettings::default()).err().unwrap();
+        assert!(err.message.contains("unknown field 'colour'"));
+
+        let err = parse_scene("material m conductor metal=silver roughness=0.2", Path::new("."), &mut RenderSettings::default()).err().unwrap();
+        assert!(err.message.contains("unknown metal 'silver'"));
+
+        let err = parse_scene("material m dielectric glass=bk7 cauchy=1.5,0.004", Path::new("."), &mut RenderSettings::default()).err().unwrap();
+        assert!(err.message.contains("either 'glass' or 'cauchy'"));
+
+        let err = parse_scene("material m dielectric ior=1.5 glass=bk7", Path::new("."), &mut RenderSettings::default()).err().unwrap();
+        assert_eq!(err.line, Some(1));
+        assert!(err.message.contains("'ior' or a dispersive 'glass' or 'cauchy'"));
+
+        let err = parse_scene("\nmaterial m dielectric ior=1.5 absorption=1,1,1 tint_distance=2", Path::new("."), &mut RenderSettings::default()).err().unwrap();
+        assert_eq!(err.line, Some(2));
+        assert!(err.message.contains("'tint_distance' needs a 'tint'"));
+
+        let err = parse_scene("camera lookfrom=0,0,1 lookat=0,0,-1\ninstance teapot scale=2", Path::new("."), &mut RenderSettings::default()).err().unwrap();
+        assert_eq!(err.line, Some(2));
+        assert!(err.message.contains("unknown mesh 'teapot'"));
+
+        assert_eq!(parse_scene("", Path::new("."), &mut RenderSettings::default()).err().unwrap().line, None);
+    }
+}
@@ -0,0 +1,185 @@
+use std::path::PathBuf;
+use std::thread;
+use crate::renderer::tile_scheduler::TileOrder;
+use crate::utility::constants::{MAX_DEPTH, ROULETTE_DEPTH, SAMPLES_PER_PIXEL, TILE_SIZE, WINDOW_HEIGHT, WINDOW_WIDTH};
+
+// Parameters of one render. Starts from the compile-time defaults, a scene
+// file `render` directive may change them and command-line flags win over both.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RenderSettings
+{
+    pub width: u32,
+    pub height: u32,
+    pub samples_per_pixel: u32,
+    pub max_depth: u32,
+    pub roulette_depth: u32,
+    pub threads: usize,
+    // Edge length of one render job and the order the jobs are queued in
+    pub tile_size: u32,
+    pub tile_order: TileOrder,
+    pub seed: u64,
+    // Trace wavelengths instead of RGB, needed for dispersion
+    pub spectral: bool,
+    // Format from the extension, .exr keeps the linear radiance
+    pub output: PathBuf
+}
+
+impl Default for RenderSettings
+{
+    fn default() -> Self
+    {
+        RenderSettings
+        {
+            width: WINDOW_WIDTH,
+            height: WINDOW_HEIGHT,
+            samples_per_pixel: SAMPLES_PER_PIXEL,
+            max_depth: MAX_DEPTH,
+            roulette_depth: ROULETTE_DEPTH,
+            threads: thread::available_parallelism().map_or(1, |n| n.get()),
+            tile_size: TILE_SIZE,
+            tile_order: TileOrder::Spiral,
+            seed: 0,
+            spectral: false,
+            output: PathBuf::from("output.png")
+        }
+    }
+}
+
+pub const USAGE: &str = "[--scene FILE] [--width N] [--height N] [--spp N] [--depth N] [--rr-depth N] [--threads N] [--tile-size N] [--tile-order scanline|spiral|hilbert] [--seed N] [--spectral] [--output FILE] [FILE]";
+
+const OPTIONS: [&str; 11] = ["--scene", "--width", "--height", "--spp", "--depth", "--rr-depth", "--threads", "--tile-size", "--tile-order", "--seed", "--output"];
+
+// Values given on the command line, `None` for everything left unset
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RenderArgs
+{
+    pub scene: Option<PathBuf>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub samples_per_pixel: Option<u32>,
+    pub max_depth: Option<u32>,
+    pub roulette_depth: Option<u32>,
+    pub threads: Option<usize>,
+    pub tile_size: Option<u32>,
+    pub tile_order: Option<TileOrder>,
+    pub seed: Option<u64>,
+    pub spectral: Option<bool>,
+    pub output: Option<PathBuf>,
+    // The single bare argument, each binary decides what it means
+    pub positional: Option<PathBuf>
+}
+
+impl RenderArgs
+{
+    pub fn parse<I: Iterator<Item = String>>(args: I) -> Result<RenderArgs, String>
+    {
+        let mut parsed = RenderArgs::default();
+        let mut args = args;
+        while let Some(arg) = args.next()
+        {
+            if !arg.starts_with("--")
+            {
+                if parsed.positional.is_some()
+                {
+                    return Err(format!("unexpected argument '{}'", arg));
+                }
+                parsed.positional = Some(PathBuf::from(arg));
+                continue;
+            }
+
+            // The one flag without a value
+            if arg == "--spectral"
+            {
+                parsed.spectral = Some(true);
+                continue;
+            }
+            if !OPTIONS.contains(&arg.as_str())
+            {
+                return Err(format!("unknown option '{}'", arg));
+            }
+            let value = args.next().ok_or_else(|| format!("{} expects a value", arg))?;
+            match arg.as_str()
+            {
+                "--scene" => parsed.scene = Some(PathBuf::from(value)),
+                "--width" => parsed.width = Some(parse_positive(&arg, &value)?),
+                "--height" => parsed.height = Some(parse_positive(&arg, &value)?),
+                "--spp" => parsed.samples_per_pixel = Some(parse_positive(&arg, &value)?),
+                "--depth" => parsed.max_depth = Some(parse_positive(&arg, &value)?),
+                "--rr-depth" => parsed.roulette_depth = Some(value.parse().map_err(|_| format!("{}: expected a non-negative integer, got '{}'", arg, value))?),
+                "--threads" => parsed.threads = Some(parse_positive::<usize>(&arg, &value)?),
+                "--tile-size" => parsed.tile_size = Some(parse_positive(&arg, &value)?),
+                "--tile-order" => parsed.tile_order = Some(TileOrder::from_name(&value).ok_or_else(|| format!("{}: expected scanline, spiral or hilbert, got '{}'", arg, value))?),
+                "--seed" => parsed.seed = Some(value.parse().map_err(|_| format!("{}: expected a non-negative integer, got '{}'", arg, value))?),
+                "--output" => parsed.output = Some(PathBuf::from(value)),
+                _ => unreachable!()
+            }
+        }
+        Ok(parsed)
+    }
+
+    // Overrides every field of `settings` that was given on the command line
+    pub fn apply(&self, settings: &mut RenderSettings)
+    {
+        if let Some(width) = self.width { settings.width = width; }
+        if let Some(height) = self.height { settings.height = height; }
+        if let Some(spp) = self.samples_per_pixel { settings.samples_per_pixel = spp; }
+        if let Some(depth) = self.max_depth { settings.max_depth = depth; }
+        if let Some(depth) = self.roulette_depth { settings.roulette_depth = depth; }
+        if let Some(threads) = self.threads { settings.threads = threads; }
+        if let Some(size) = self.tile_size { settings.tile_size = size; }
+        if let Some(order) = self.tile_order { settings.tile_order = order; }
+        if let Some(seed) = self.seed { settings.seed = seed; }
+        if let Some(spectral) = self.spectral { settings.spectral = spectral; }
+        if let Some(output) = &self.output { settings.output = output.clone(); }
+    }
+}
+
+fn parse_positive<T: std::str::FromStr + PartialOrd + Default>(option: &str, value: &str) -> Result<T, String>
+{
+    match value.parse::<T>()
+    {
+        Ok(n) if n > T::default() => Ok(n),
+        _ => Err(format!("{}: expected a positive integer, got '{}'", option, value))
+    }
+}
+
+#[cfg(test)]
+mod test
+{
+    use super::*;
+
+    fn args(line: &str) -> Result<RenderArgs, String>
+    {
+        RenderArgs::parse(line.split_whitespace().map(String::from))
+    }
+
+    #[test]
+    fn test_args_override_settings()
+    {
+        let parsed = args("--spp 64 --width 320 --seed 7 --rr-depth 0 --tile-size 16 --tile-order hilbert --spectral out.png").unwrap();
+        assert_eq!(parsed.positional, Some(PathBuf::from("out.png")));
+
+        let mut settings = RenderSettings::default();
+        settings.height = 200;
+        parsed.apply(&mut settings);
+
+        assert_eq!(settings.samples_per_pixel, 64);
+        assert_eq!((settings.width, settings.height), (320, 200));
+        assert_eq!(settings.seed, 7);
+        assert_eq!(settings.roulette_depth, 0);
+        assert_eq!((settings.tile_size, settings.tile_order), (16, TileOrder::Hilbert));
+        assert!(settings.spectral);
+        assert_eq!(settings.max_depth, MAX_DEPTH);
+    }
+
+    #[test]
+    fn test_args_errors()
+    {
+        assert!(args("--spp 0").is_err());
+        assert!(args("--spp").is_err());
+        assert!(args("--bogus 1").is_err());
+        assert!(args("--tile-size 0").is_err());
+        assert!(args("--tile-order random").is_err());
+        assert!(args("a.scene b.scene").is_err());
+    }
+}
@@ -0,0 +1,289 @@
+use cgmath::{Vector3, Vector4};
+
+// Support for spectral rendering. A spectral path carries its colour as values
+// at four wavelengths instead of RGB; materials and textures stay RGB and are
+// upsampled to a spectrum wherever the path meets them.
+
+// Visible range every path samples its wavelengths from, in nanometres
+pub const LAMBDA_MIN: f32 = 380.0;
+pub const LAMBDA_MAX: f32 = 720.0;
+
+// Wavelength of the sodium d-line, where glasses quote their plain ior
+pub const D_LINE: f32 = 587.6;
+
+// Wavelengths of one spectral path: a uniformly drawn hero and three companions
+// spaced evenly across the range, wrapping around (Wilkie et al. 2014)
+#[derive(Copy, Clone, Debug)]
+pub struct SampledWavelengths
+{
+    _lambda: [f32; 4],
+    _secondary_terminated: bool
+}
+
+impl SampledWavelengths
+{
+    pub fn sample(u: f32) -> Self
+    {
+        let range = LAMBDA_MAX - LAMBDA_MIN;
+        let hero = LAMBDA_MIN + u * range;
+        let mut lambda = [hero; 4];
+        for (i, l) in lambda.iter_mut().enumerate().skip(1)
+        {
+            *l = hero + i as f32 * range / 4.0;
+            if *l > LAMBDA_MAX
+            {
+                *l -= range;
+            }
+        }
+
+        SampledWavelengths
+        {
+            _lambda: lambda,
+            _secondary_terminated: false
+        }
+    }
+
+    pub fn hero(&self) -> f32
+    {
+        self._lambda[0]
+    }
+
+    pub fn lambda(&self) -> [f32; 4]
+    {
+        self._lambda
+    }
+}
+
+// How a path carries colour. Both variants use four lanes so the integrator is
+// the same code either way, RGB simply leaves the last lane at zero.
+#[derive(Copy, Clone, Debug)]
+pub enum PathColor
+{
+    Rgb,
+    Spectral(SampledWavelengths)
+}
+
+impl PathColor
+{
+    // Wavelength rays of this path are tagged with, `None` for RGB
+    pub fn wavelength(&self) -> Option<f32>
+    {
+        match self
+        {
+            PathColor::Rgb => None,
+            PathColor::Spectral(wavelengths) => Some(wavelengths.hero())
+        }
+    }
+
+    // An RGB reflectance or radiance in the representation of this path
+    pub fn lift(&self, rgb: Vector3<f32>) -> Vector4<f32>
+    {
+        match self
+        {
+            PathColor::Rgb => rgb.extend(0.0),
+            PathColor::Spectral(wavelengths) =>
+            {
+                let l = wavelengths.lambda();
+                Vector4::new(rgb_to_spectrum(rgb, l[0]), rgb_to_spectrum(rgb, l[1]),
+                             rgb_to_spectrum(rgb, l[2]), rgb_to_spectrum(rgb, l[3]))
+            }
+        }
+    }
+
+    // Once a wavelength dependent interface has bent the path only the hero can
+    // follow it. It takes over the weight of the other three so the estimate of
+    // every wavelength stays unbiased.
+    pub fn terminate_secondary(&mut self, throughput: &mut Vector4<f32>)
+    {
+        if let PathColor::Spectral(wavelengths) = self
+        {
+            if !wavelengths._secondary_terminated
+            {
+                wavelengths._secondary_terminated = true;
+                *throughput = Vector4::new(throughput.x * 4.0, 0.0, 0.0, 0.0);
+            }
+        }
+    }
+
+    // Linear sRGB of the radiance a path gathered
+    pub fn resolve(&self, radiance: Vector4<f32>) -> Vector3<f32>
+    {
+        match self
+        {
+            PathColor::Rgb => radiance.truncate(),
+            PathColor::Spectral(wavelengths) =>
+            {
+                let l = wavelengths.lambda();
+                let xyz = radiance.x * cie_xyz(l[0]) + radiance.y * cie_xyz(l[1])
+                        + radiance.z * cie_xyz(l[2]) + radiance.w * cie_xyz(l[3]);
+                xyz_to_linear_srgb(xyz * ((LAMBDA_MAX - LAMBDA_MIN) / 4.0))
+            }
+        }
+    }
+}
+
+// Piecewise Gaussian used by the CIE fit below, with different widths either side of the peak
+fn lobe(lambda: f32, mu: f32, sigma_low: f32, sigma_high: f32) -> f32
+{
+    let sigma = if lambda < mu { sigma_low } else { sigma_high };
+    let t = (lambda - mu) / sigma;
+    (-0.5 * t * t).exp()
+}
+
+// CIE 1931 2 degree colour matching functions, multi-lobe fit of Wyman et al. 2013
+pub fn cie_xyz(lambda: f32) -> Vector3<f32>
+{
+    let x = 1.056 * lobe(lambda, 599.8, 37.9, 31.0) + 0.362 * lobe(lambda, 442.0, 16.0, 26.7) - 0.065 * lobe(lambda, 501.1, 20.4, 26.2);
+    let y = 0.821 * lobe(lambda, 568.8, 46.9, 40.5) + 0.286 * lobe(lambda, 530.9, 16.3, 31.1);
+    let z = 1.217 * lobe(lambda, 437.0, 11.8, 36.0) + 0.681 * lobe(lambda, 459.0, 26.0, 13.8);
+    Vector3::new(x, y, z)
+}
+
+// Linear sRGB of the constant spectrum 1 over [LAMBDA_MIN, LAMBDA_MAX]. Dividing
+// by it white balances the film, a grey albedo under a white light stays grey.
+const WHITE_RGB: [f32; 3] = [128.359, 101.528, 97.066];
+
+// XYZ integrated over the sampled range to linear sRGB (D65 primaries). The
+// matrix is quoted as published by Bruce Lindbloom, digits beyond f32 included.
+#[allow(clippy::excessive_precision)]
+pub fn xyz_to_linear_srgb(xyz: Vector3<f32>) -> Vector3<f32>
+{
+    let r = 3.2404542 * xyz.x - 1.5371385 * xyz.y - 0.4985314 * xyz.z;
+    let g = -0.9692660 * xyz.x + 1.8760108 * xyz.y + 0.0415560 * xyz.z;
+    let b = 0.0556434 * xyz.x - 0.2040259 * xyz.y + 1.0572252 * xyz.z;
+    Vector3::new(r / WHITE_RGB[0], g / WHITE_RGB[1], b / WHITE_RGB[2])
+}
+
+// Smits 1999 basis spectra, ten equal bins over [LAMBDA_MIN, LAMBDA_MAX]
+const WHITE: [f32; 10] = [1.0000, 1.0000, 0.9999, 0.9993, 0.9992, 0.9998, 1.0000, 1.0000, 1.0000, 1.0000];
+const CYAN: [f32; 10] = [0.9710, 0.9426, 1.0007, 1.0007, 1.0007, 1.0007, 0.1564, 0.0000, 0.0000, 0.0000];
+const MAGENTA: [f32; 10] = [1.0000, 1.0000, 0.9685, 0.2229, 0.0000, 0.0458, 0.8369, 1.0000, 1.0000, 0.9959];
+const YELLOW: [f32; 10] = [0.0001, 0.0000, 0.1088, 0.6651, 1.0000, 1.0000, 0.9996, 0.9586, 0.9685, 0.9840];
+const RED: [f32; 10] = [0.1012, 0.0515, 0.0000, 0.0000, 0.0000, 0.0000, 0.8325, 1.0149, 1.0149, 1.0149];
+const GREEN: [f32; 10] = [0.0000, 0.0000, 0.0273, 0.7937, 1.0000, 0.9418, 0.1719, 0.0000, 0.0000, 0.0025];
+const BLUE: [f32; 10] = [1.0000, 1.0000, 0.8916, 0.3323, 0.0000, 0.0000, 0.0003, 0.0369, 0.0483, 0.0496];
+
+// Value at `lambda` of a smooth spectrum with roughly the colour `rgb`: white
+// for the smallest channel, then the secondary and primary that make up the rest
+pub fn rgb_to_spectrum(rgb: Vector3<f32>, lambda: f32) -> f32
+{
+    let bin = (((lambda - LAMBDA_MIN) / (LAMBDA_MAX - LAMBDA_MIN) * 10.0) as usize).min(9);
+    let (r, g, b) = (rgb.x, rgb.y, rgb.z);
+
+    if r <= g && r <= b
+    {
+        let base = r * WHITE[bin];
+        if g <= b { base + (g - r) * CYAN[bin] + (b - g) * BLUE[bin] } else { base + (b - r) * CYAN[bin] + (g - b) * GREEN[bin] }
+    }
+    else if g <= r && g <= b
+    {
+        let base = g * WHITE[bin];
+        if r <= b { base + (r - g) * MAGENTA[bin] + (b - r) * BLUE[bin] } else { base + (b - g) * MAGENTA[bin] + (r - b) * RED[bin] }
+    }
+    else
+    {
+        let base = b * WHITE[bin];
+        if r <= g { base + (r - b) * YELLOW[bin] + (g - r) * GREEN[bin] } else { base + (g - b) * YELLOW[bin] + (r - g) * RED[bin] }
+    }
+}
+
+// Wavelength dependent index of refraction of a glass, wavelengths in nanometres
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Dispersion
+{
+    // n = a + b / lambda^2, lambda in micrometres
+    Cauchy(f32, f32),
+    // n^2 = 1 + sum b_i lambda^2 / (lambda^2 - c_i), lambda in micrometres
+    Sellmeier([f32; 3], [f32; 3])
+}
+
+impl Dispersion
+{
+    pub fn ior(&self, lambda: f32) -> f32
+    {
+        let l2 = (lambda * 1e-3) * (lambda * 1e-3);
+        match self
+        {
+            Dispersion::Cauchy(a, b) => a + b / l2,
+            Dispersion::Sellmeier(b, c) => (1.0 + (0..3).map(|i| b[i] * l2 / (l2 - c[i])).sum::<f32>()).sqrt()
+        }
+    }
+
+    // Sellmeier coefficients of a few common glasses, by name. Copied verbatim
+    // from the Schott catalogue (Malitson 1965 for fused silica), so some
+    // carry more digits than an f32 holds.
+    #[allow(clippy::excessive_precision)]
+    pub fn glass(name: &str) -> Option<Dispersion>
+    {
+        match name
+        {
+            "bk7" => Some(Dispersion::Sellmeier([1.03961212, 0.231792344, 1.01046945], [0.00600069867, 0.0200179144, 103.560653])),
+            "fused_silica" => Some(Dispersion::Sellmeier([0.6961663, 0.4079426, 0.8974794], [0.00467914826, 0.0135120631, 97.9340025])),
+            "sf11" => Some(Dispersion::Sellmeier([1.73759695, 0.313747346, 1.89878101], [0.013188707, 0.0623068142, 155.23629])),
+            "diamond" => Some(Dispersion::Sellmeier([0.3306, 4.3356, 0.0], [0.030625, 0.011236, 0.0])),
+            _ => None
+        }
+    }
+}
+
+#[cfg(test)]
+mod test
+{
+    use cgmath::Array;
+    use super::*;
+
+    // Film response to the constant spectrum `rgb` is lifted to, averaged over many wavelength sets
+    fn round_trip(rgb: Vector3<f32>) -> Vector3<f32>
+    {
+        let n = 10_000;
+        (0..n).map(|i|
+        {
+            let path = PathColor::Spectral(SampledWavelengths::sample((i as f32 + 0.5) / n as f32));
+            path.resolve(path.lift(rgb))
+        }).fold(Vector3::from_value(0.0), |a, b| a + b) / n as f32
+    }
+
+    #[test]
+    fn test_rgb_round_trip()
+    {
+        let white = round_trip(Vector3::from_value(1.0));
+        assert!((white - Vector3::from_value(1.0)).map(f32::abs).sum() < 3e-3, "{:?}", white);
+
+        // Smits spectra are only approximately the primaries but keep their hue
+        let red = round_trip(Vector3::new(1.0, 0.0, 0.0));
+        assert!(red.x > 0.7 && red.y < 0.1 && red.z < 0.1, "{:?}", red);
+        let grey = round_trip(Vector3::from_value(0.25));
+        assert!((grey - Vector3::from_value(0.25)).map(f32::abs).sum() < 1e-3, "{:?}", grey);
+    }
+
+    #[test]
+    fn test_wavelengths_and_termination()
+    {
+        let wavelengths = SampledWavelengths::sample(0.9);
+        let mut lambda = wavelengths.lambda();
+        lambda.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        for pair in lambda.windows(2)
+        {
+            assert!((pair[1] - pair[0] - 85.0).abs() < 1e-3);
+        }
+        assert!(lambda.iter().all(|l| (LAMBDA_MIN..=LAMBDA_MAX).contains(l)));
+
+        let mut path = PathColor::Spectral(wavelengths);
+        let mut throughput = Vector4::from_value(0.5);
+        path.terminate_secondary(&mut throughput);
+        path.terminate_secondary(&mut throughput);
+        assert_eq!(throughput, Vector4::new(2.0, 0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn test_dispersion()
+    {
+        // BK7 is 1.5168 at the d-line and bends blue light more than red
+        let bk7 = Dispersion::glass("bk7").unwrap();
+        assert!((bk7.ior(D_LINE) - 1.5168).abs() < 1e-3);
+        assert!(bk7.ior(450.0) > bk7.ior(650.0));
+        assert!((Dispersion::glass("diamond").unwrap().ior(D_LINE) - 2.417).abs() < 5e-3);
+        assert!((Dispersion::Cauchy(1.5, 0.01).ior(1000.0) - 1.51).abs() < 1e-6);
+        assert!(Dispersion::glass("unobtainium").is_none());
+    }
+}
@@ -1,6 +1,8 @@
 use std::sync::Arc;
-use cgmath::{Vector3, dot};
-use crate::renderer::custom_function::{length_squared, set_face_normal};
+use cgmath::{Array, Vector3, dot};
+use crate::renderer::aabb::Aabb;
+use crate::renderer::custom_function::{length_squared, orthonormal_basis, random_double, set_face_normal, unit_vector3};
+use crate::utility::constants::PI;
 use crate::renderer::hittable::{HitRecord, Hittable};
 use crate::renderer::interval::Interval;
 use crate::renderer::material::{Material};
@@ -8,22 +10,64 @@ use crate::renderer::ray::Ray;
 
 pub struct Sphere
 {
+    // Centre at time 0 and its displacement until time 1
     _center: Vector3<f32>,
+    _motion: Vector3<f32>,
     _radius: f32,
     _material: Arc<dyn Material>,
+    _bbox: Aabb
 }
 
 impl Sphere
 {
     pub fn new(p: Vector3<f32>, r: f32, material: Arc<dyn Material>) -> Self
     {
+        // A negative radius turns the normals inwards, the box must not be
+        let rvec = Vector3::from_value(r.abs());
         Sphere
         {
             _center: p,
+            _motion: Vector3::from_value(0.0),
             _radius: r,
-            _material: material
+            _material: material,
+            _bbox: Aabb::from_points(p - rvec, p + rvec)
         }
     }
+
+    // Moves linearly from `p0` at time 0 to `p1` at time 1
+    pub fn moving(p0: Vector3<f32>, p1: Vector3<f32>, r: f32, material: Arc<dyn Material>) -> Self
+    {
+        let rvec = Vector3::from_value(r.abs());
+        let box0 = Aabb::from_points(p0 - rvec, p0 + rvec);
+        let box1 = Aabb::from_points(p1 - rvec, p1 + rvec);
+        Sphere
+        {
+            _center: p0,
+            _motion: p1 - p0,
+            _radius: r,
+            _material: material,
+            _bbox: Aabb::surrounding(&box0, &box1)
+        }
+    }
+
+    fn center(&self, time: f32) -> Vector3<f32>
+    {
+        self._center + time * self._motion
+    }
+}
+
+impl Sphere
+{
+    // p: a given point on the sphere of radius one, centered at the origin.
+    // u: returned value [0,1] of angle around the Y axis from X=-1.
+    // v: returned value [0,1] of angle from Y=-1 to Y=+1.
+    fn get_sphere_uv(p: Vector3<f32>) -> (f32, f32)
+    {
+        let theta = (-p.y).clamp(-1.0, 1.0).acos();
+        let phi = (-p.z).atan2(p.x) + PI;
+
+        (phi / (2.0 * PI), theta / PI)
+    }
 }
 
 impl Hittable for Sphere
@@ -66,7 +110,8 @@ impl Hittable for Sphere
 
     fn hit(&self, ray: Ray, ray_t: Interval) -> Option<HitRecord>
     {
-        let oc = ray.origin() - self._center;
+        let center = self.center(ray.time());
+        let oc = ray.origin() - center;
         let a = length_squared(ray.direction());
         let b = dot(oc, ray.direction());
         let c = length_squared(oc) - self._radius * self._radius;
@@ -91,10 +136,59 @@ impl Hittable for Sphere
                 }
             }
 
-            let outward_normal = (ray.at(root) - self._center.clone()) / self._radius.clone();
+            let outward_normal = (ray.at(root) - center) / self._radius.clone();
             let (normal, front_face) = set_face_normal(ray, outward_normal);
+            let (u, v) = Sphere::get_sphere_uv(outward_normal);
+
+            Some(HitRecord{ _t: root, _point: ray.at(root), _normal: normal, _material: self._material.clone(), _u: u, _v: v, _front_face: front_face})
+        }
+    }
+
+    fn bounding_box(&self) -> Aabb
+    {
+        self._bbox
+    }
+
+    // Uniform over the cone of directions the sphere covers as seen from `origin`.
+    // Moving spheres are sampled where they are at time 0.
+    fn pdf_value(&self, origin: Vector3<f32>, direction: Vector3<f32>) -> f32
+    {
+        let distance_squared = length_squared(self._center - origin);
+        let radius_squared = self._radius * self._radius;
+        // From inside every direction hits, the cone is undefined
+        if distance_squared <= radius_squared
+        {
+            return 0.0;
+        }
+        if self.hit(Ray::new(origin, direction), Interval::new(0.001, f32::INFINITY)).is_none()
+        {
+            return 0.0;
+        }
 
-            Some(HitRecord{ _t: root, _point: ray.at(root), _normal: normal, _material: self._material.clone(), _front_face: front_face})
+        let cos_theta_max = (1.0 - radius_squared / distance_squared).sqrt();
+        let solid_angle = 2.0 * PI * (1.0 - cos_theta_max);
+        1.0 / solid_angle
+    }
+
+    fn random(&self, origin: Vector3<f32>) -> Vector3<f32>
+    {
+        let direction = self._center - origin;
+        let distance_squared = length_squared(direction);
+        let radius_squared = self._radius * self._radius;
+        if distance_squared <= radius_squared
+        {
+            return direction;
         }
+
+        let w = unit_vector3(direction);
+        let (u, v) = orthonormal_basis(w);
+
+        let r1 = random_double();
+        let r2 = random_double();
+        let z = 1.0 + r2 * ((1.0 - radius_squared / distance_squared).sqrt() - 1.0);
+        let phi = 2.0 * PI * r1;
+        let sin_theta = (1.0 - z * z).max(0.0).sqrt();
+
+        phi.cos() * sin_theta * u + phi.sin() * sin_theta * v + z * w
     }
 }
\ No newline at end of file
@@ -0,0 +1,185 @@
+use std::path::Path;
+use std::sync::Arc;
+use cgmath::Vector3;
+use crate::renderer::custom_function::srgb_to_linear;
+use crate::renderer::interval::Interval;
+use crate::renderer::perlin::Perlin;
+
+pub trait Texture: Sync + Send
+{
+    // Colour at surface coordinates (u, v) and world position p
+    fn value(&self, u: f32, v: f32, p: Vector3<f32>) -> Vector3<f32>;
+}
+
+#[derive(Clone)]
+pub struct SolidColor
+{
+    albedo: Vector3<f32>
+}
+
+impl SolidColor
+{
+    pub fn new(albedo: Vector3<f32>) -> Self
+    {
+        SolidColor
+        {
+            albedo
+        }
+    }
+}
+
+impl Texture for SolidColor
+{
+    fn value(&self, _u: f32, _v: f32, _p: Vector3<f32>) -> Vector3<f32>
+    {
+        self.albedo
+    }
+}
+
+// Solid 3D checker pattern, `scale` is the edge length of one cell in world units
+pub struct CheckerTexture
+{
+    inv_scale: f32,
+    even: Arc<dyn Texture>,
+    odd: Arc<dyn Texture>
+}
+
+impl CheckerTexture
+{
+    pub fn new(scale: f32, even: Arc<dyn Texture>, odd: Arc<dyn Texture>) -> Self
+    {
+        CheckerTexture
+        {
+            inv_scale: 1.0 / scale,
+            even,
+            odd
+        }
+    }
+
+    pub fn from_colors(scale: f32, even: Vector3<f32>, odd: Vector3<f32>) -> Self
+    {
+        CheckerTexture::new(scale, Arc::new(SolidColor::new(even)), Arc::new(SolidColor::new(odd)))
+    }
+}
+
+impl Texture for CheckerTexture
+{
+    fn value(&self, u: f32, v: f32, p: Vector3<f32>) -> Vector3<f32>
+    {
+        let x = (self.inv_scale * p.x).floor() as i64;
+        let y = (self.inv_scale * p.y).floor() as i64;
+        let z = (self.inv_scale * p.z).floor() as i64;
+
+        if (x + y + z) % 2 == 0
+        {
+            self.even.value(u, v, p)
+        }
+        else
+        {
+            self.odd.value(u, v, p)
+        }
+    }
+}
+
+// Texture looked up by (u, v), the image is assumed to be sRGB encoded
+pub struct ImageTexture
+{
+    width: usize,
+    height: usize,
+    pixels: Vec<Vector3<f32>>
+}
+
+impl ImageTexture
+{
+    pub fn load(path: &Path) -> Result<Self, String>
+    {
+        let img = image::open(path).map_err(|err| format!("failed to read {}: {}", path.display(), err))?.to_rgb();
+        let (width, height) = img.dimensions();
+        if width == 0 || height == 0
+        {
+            return Err(format!("{} is empty", path.display()));
+        }
+
+        let pixels = img.pixels().map(|p| Vector3::new(srgb_to_linear(p.0[0]), srgb_to_linear(p.0[1]), srgb_to_linear(p.0[2]))).collect();
+
+        Ok(ImageTexture
+        {
+            width: width as usize,
+            height: height as usize,
+            pixels
+        })
+    }
+}
+
+impl Texture for ImageTexture
+{
+    fn value(&self, u: f32, v: f32, _p: Vector3<f32>) -> Vector3<f32>
+    {
+        let limit = Interval::new(0.0, 1.0);
+        let u = limit.clamp(u);
+        // Flip V, image rows run top to bottom
+        let v = 1.0 - limit.clamp(v);
+
+        let i = ((u * self.width as f32) as usize).min(self.width - 1);
+        let j = ((v * self.height as f32) as usize).min(self.height - 1);
+
+        self.pixels[i + j * self.width]
+    }
+}
+
+// Marble like pattern: a sine wave along z phase-shifted by Perlin turbulence
+pub struct NoiseTexture
+{
+    noise: Perlin,
+    scale: f32
+}
+
+impl NoiseTexture
+{
+    pub fn new(scale: f32) -> Self
+    {
+        NoiseTexture
+        {
+            noise: Perlin::new(),
+            scale
+        }
+    }
+}
+
+impl Texture for NoiseTexture
+{
+    fn value(&self, _u: f32, _v: f32, p: Vector3<f32>) -> Vector3<f32>
+    {
+        let s = 0.5 * (1.0 + (self.scale * p.z + 10.0 * self.noise.turb(p, 7)).sin());
+        Vector3::new(s, s, s)
+    }
+}
+
+#[cfg(test)]
+mod test
+{
+    use super::*;
+
+    #[test]
+    fn test_checker()
+    {
+        let white = Vector3::new(1.0, 1.0, 1.0);
+        let black = Vector3::new(0.0, 0.0, 0.0);
+        let checker = CheckerTexture::from_colors(1.0, white, black);
+
+        assert_eq!(checker.value(0.0, 0.0, Vector3::new(0.5, 0.5, 0.5)), white);
+        assert_eq!(checker.value(0.0, 0.0, Vector3::new(1.5, 0.5, 0.5)), black);
+        assert_eq!(checker.value(0.0, 0.0, Vector3::new(-0.5, 0.5, 0.5)), black);
+    }
+
+    #[test]
+    fn test_noise_range()
+    {
+        let noise = NoiseTexture::new(4.0);
+        for i in 0..100
+        {
+            let c = noise.value(0.0, 0.0, Vector3::new(i as f32 * 0.37, i as f32 * 0.11, i as f32 * 0.05));
+            assert!(c.x >= 0.0 && c.x <= 1.0);
+        }
+    }
+}
@@ -0,0 +1,225 @@
+use std::sync::{Arc, Mutex};
+use crate::renderer::film::Film;
+use crate::renderer::scene::Scene;
+use crate::renderer::thread_pool::ThreadPool;
+
+// Order in which tiles are handed to the workers
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum TileOrder
+{
+    // Row by row from the top left
+    Scanline,
+    // Rings around the image centre, the interesting part shows up first
+    Spiral,
+    // Along a Hilbert curve, neighbouring tiles are rendered close in time
+    Hilbert
+}
+
+impl TileOrder
+{
+    pub fn from_name(name: &str) -> Option<TileOrder>
+    {
+        match name
+        {
+            "scanline" => Some(TileOrder::Scanline),
+            "spiral" => Some(TileOrder::Spiral),
+            "hilbert" => Some(TileOrder::Hilbert),
+            _ => None
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Tile
+{
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32
+}
+
+pub fn generate_tiles(width: u32, height: u32, tile_size: u32, order: TileOrder) -> Vec<Tile>
+{
+    assert!(tile_size > 0);
+    let tiles_x = width.div_ceil(tile_size);
+    let tiles_y = height.div_ceil(tile_size);
+
+    let mut grid: Vec<(u32, u32)> = (0..tiles_y).flat_map(|ty| (0..tiles_x).map(move |tx| (tx, ty))).collect();
+
+    match order
+    {
+        TileOrder::Scanline => {}
+        TileOrder::Spiral =>
+        {
+            // Chebyshev ring around the centre, then the angle within the ring
+            let cx = (tiles_x as f32 - 1.0) / 2.0;
+            let cy = (tiles_y as f32 - 1.0) / 2.0;
+            let key = |&(tx, ty): &(u32, u32)| {
+                let dx = tx as f32 - cx;
+                let dy = ty as f32 - cy;
+                (dx.abs().max(dy.abs()), dy.atan2(dx))
+            };
+            grid.sort_by(|a, b| key(a).partial_cmp(&key(b)).unwrap_or(std::cmp::Ordering::Equal));
+        }
+        TileOrder::Hilbert =>
+        {
+            let n = tiles_x.max(tiles_y).next_power_of_two();
+            grid.sort_by_key(|&(tx, ty)| hilbert_index(n, tx, ty));
+        }
+    }
+
+    grid.iter().map(|&(tx, ty)| {
+        let x = tx * tile_size;
+        let y = ty * tile_size;
+        Tile { x, y, width: tile_size.min(width - x), height: tile_size.min(height - y) }
+    }).collect()
+}
+
+// Distance of (x, y) along the Hilbert curve filling an n x n grid, n a power of two
+fn hilbert_index(n: u32, x: u32, y: u32) -> u64
+{
+    let (mut x, mut y) = (x, y);
+    let mut d: u64 = 0;
+    let mut s = n / 2;
+    while s > 0
+    {
+        let rx = ((x & s) > 0) as u32;
+        let ry = ((y & s) > 0) as u32;
+        d += s as u64 * s as u64 * ((3 * rx) ^ ry) as u64;
+
+        // Rotate the quadrant
+        if ry == 0
+        {
+            if rx == 1
+            {
+                x = n - 1 - x;
+                y = n - 1 - y;
+            }
+            std::mem::swap(&mut x, &mut y);
+        }
+        s /= 2;
+    }
+    d
+}
+
+// Renders every pixel with all of the camera's samples in a single pass
+pub fn render_tiles(thread_pool: &ThreadPool, scene: &Arc<Scene>, film: &Arc<Mutex<Film>>, tile_size: u32, order: TileOrder)
+{
+    let samples = scene.camera().samples_per_pixel();
+    render_progressive(thread_pool, scene, film, tile_size, order, samples);
+}
+
+// Splits the camera's samples per pixel into passes of `samples_per_pass` over
+// the whole image and queues one job per tile and pass. Queue order only fixes
+// the order in which jobs start, so tiles of the next pass may finish while the
+// current one is still running. The film counts samples per pixel, so the
+// displayed average stays correct and refines gradually either way.
+pub fn render_progressive(thread_pool: &ThreadPool, scene: &Arc<Scene>, film: &Arc<Mutex<Film>>, tile_size: u32, order: TileOrder, samples_per_pass: u32)
+{
+    let total_samples = scene.camera().samples_per_pixel();
+    assert!(samples_per_pass > 0);
+    let (width, height) = {
+        let film = film.lock().unwrap();
+        (film.width(), film.height())
+    };
+    let tiles = generate_tiles(width, height, tile_size, order);
+
+    let mut first_sample = 0;
+    while first_sample < total_samples
+    {
+        let count = samples_per_pass.min(total_samples - first_sample);
+        for &tile in &tiles
+        {
+            let local_scene = Arc::clone(scene);
+            let local_film = Arc::clone(film);
+            thread_pool.exec(Box::new(move || {
+                // Trace into a local buffer and take the film lock once per tile
+                let mut sums = Vec::with_capacity((tile.width * tile.height) as usize);
+                for j in tile.y..tile.y + tile.height
+                {
+                    for i in tile.x..tile.x + tile.width
+                    {
+                        sums.push(local_scene.sample_pixel(width, height, i, j, first_sample, count));
+                    }
+                }
+
+                local_film.lock().unwrap().add_samples(tile.x, tile.y, tile.width, &sums, count);
+            }));
+        }
+        first_sample += count;
+    }
+}
+
+#[cfg(test)]
+mod test
+{
+    use super::*;
+
+    fn assert_covers_image(tiles: &[Tile], width: u32, height: u32)
+    {
+        let mut covered = vec![0; (width * height) as usize];
+        for tile in tiles
+        {
+            for j in tile.y..tile.y + tile.height
+            {
+                for i in tile.x..tile.x + tile.width
+                {
+                    covered[(i + j * width) as usize] += 1;
+                }
+            }
+        }
+        assert!(covered.iter().all(|&c| c == 1));
+    }
+
+    #[test]
+    fn test_tiles_cover_image_once()
+    {
+        for &order in &[TileOrder::Scanline, TileOrder::Spiral, TileOrder::Hilbert]
+        {
+            let tiles = generate_tiles(100, 37, 16, order);
+            assert_eq!(tiles.len(), 7 * 3);
+            assert_covers_image(&tiles, 100, 37);
+        }
+    }
+
+    #[test]
+    fn test_spiral_starts_at_centre()
+    {
+        let tiles = generate_tiles(90, 90, 30, TileOrder::Spiral);
+        assert_eq!((tiles[0].x, tiles[0].y), (30, 30));
+    }
+
+    #[test]
+    fn test_progressive_matches_single_pass()
+    {
+        let scene = Arc::new(crate::renderer::render_backend::build_scene(16, 12));
+        let single = Arc::new(Mutex::new(Film::new(16, 12)));
+        let progressive = Arc::new(Mutex::new(Film::new(16, 12)));
+
+        let thread_pool = ThreadPool::new(2);
+        render_tiles(&thread_pool, &scene, &single, 8, TileOrder::Scanline);
+        render_progressive(&thread_pool, &scene, &progressive, 8, TileOrder::Hilbert, 4);
+        drop(thread_pool);
+
+        let single = single.lock().unwrap();
+        let progressive = progressive.lock().unwrap();
+        assert_eq!(progressive.min_samples(), scene.camera().samples_per_pixel());
+        for (a, b) in single.resolve().iter().zip(progressive.resolve().iter())
+        {
+            let d = a - b;
+            assert!(d.x.abs() < 1e-4 && d.y.abs() < 1e-4 && d.z.abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn test_hilbert_steps_are_adjacent()
+    {
+        let tiles = generate_tiles(128, 128, 16, TileOrder::Hilbert);
+        for pair in tiles.windows(2)
+        {
+            let dx = (pair[0].x as i64 - pair[1].x as i64).abs();
+            let dy = (pair[0].y as i64 - pair[1].y as i64).abs();
+            assert_eq!(dx + dy, 16);
+        }
+    }
+}
@@ -0,0 +1,410 @@
+use std::sync::Arc;
+use cgmath::{Deg, Matrix, Matrix4, SquareMatrix, Vector3};
+use crate::renderer::aabb::Aabb;
+use crate::renderer::custom_function::{degrees_to_radians, length, unit_vector3};
+use crate::renderer::hittable::{HitRecord, Hittable};
+use crate::renderer::interval::Interval;
+use crate::renderer::ray::Ray;
+
+// All wrappers below keep the wrapped object behind an `Arc` and move the ray
+// into its local frame instead of copying geometry, so one mesh can be placed
+// any number of times for the cost of a matrix each.
+
+// Box around the eight corners of `bbox` after mapping them through `f`
+fn transform_box(bbox: &Aabb, f: impl Fn(Vector3<f32>) -> Vector3<f32>) -> Aabb
+{
+    let (lo, hi) = (bbox.min(), bbox.max());
+    let mut min = Vector3::new(f32::INFINITY, f32::INFINITY, f32::INFINITY);
+    let mut max = -min;
+    for i in 0..8
+    {
+        let corner = Vector3::new(if i & 1 == 0 { lo.x } else { hi.x },
+                                  if i & 2 == 0 { lo.y } else { hi.y },
+                                  if i & 4 == 0 { lo.z } else { hi.z });
+        let p = f(corner);
+        for axis in 0..3
+        {
+            min[axis] = min[axis].min(p[axis]);
+            max[axis] = max[axis].max(p[axis]);
+        }
+    }
+    Aabb::from_points(min, max)
+}
+
+pub struct Translate
+{
+    _object: Arc<dyn Hittable>,
+    _offset: Vector3<f32>,
+    _bbox: Aabb
+}
+
+impl Translate
+{
+    pub fn new(object: Arc<dyn Hittable>, offset: Vector3<f32>) -> Self
+    {
+        let bbox = object.bounding_box();
+        Translate
+        {
+            _object: object,
+            _offset: offset,
+            _bbox: Aabb::from_points(bbox.min() + offset, bbox.max() + offset)
+        }
+    }
+}
+
+impl Hittable for Translate
+{
+    fn hit(&self, ray: Ray, ray_t: Interval) -> Option<HitRecord>
+    {
+        let moved = Ray::with_time(ray.origin() - self._offset, ray.direction(), ray.time());
+
+        let mut rec = self._object.hit(moved, ray_t)?;
+        rec._point += self._offset;
+        Some(rec)
+    }
+
+    fn bounding_box(&self) -> Aabb
+    {
+        self._bbox
+    }
+
+    fn pdf_value(&self, origin: Vector3<f32>, direction: Vector3<f32>) -> f32
+    {
+        self._object.pdf_value(origin - self._offset, direction)
+    }
+
+    fn random(&self, origin: Vector3<f32>) -> Vector3<f32>
+    {
+        self._object.random(origin - self._offset)
+    }
+}
+
+// Rotation around one coordinate axis, shared by RotateX, RotateY and RotateZ
+struct AxisRotation
+{
+    _object: Arc<dyn Hittable>,
+    // The two axes spanning the plane of rotation, in right-handed order
+    _i: usize,
+    _j: usize,
+    _sin: f32,
+    _cos: f32,
+    _bbox: Aabb
+}
+
+impl AxisRotation
+{
+    fn new(object: Arc<dyn Hittable>, axis: usize, angle: f32) -> Self
+    {
+        let radians = degrees_to_radians(angle);
+        let mut rotation = AxisRotation
+        {
+            _object: object,
+            _i: (axis + 1) % 3,
+            _j: (axis + 2) % 3,
+            _sin: radians.sin(),
+            _cos: radians.cos(),
+            _bbox: Aabb::empty()
+        };
+        rotation._bbox = transform_box(&rotation._object.bounding_box(), |p| rotation.to_world(p));
+        rotation
+    }
+
+    fn to_world(&self, v: Vector3<f32>) -> Vector3<f32>
+    {
+        let mut r = v;
+        r[self._i] = self._cos * v[self._i] - self._sin * v[self._j];
+        r[self._j] = self._sin * v[self._i] + self._cos * v[self._j];
+        r
+    }
+
+    fn to_object(&self, v: Vector3<f32>) -> Vector3<f32>
+    {
+        let mut r = v;
+        r[self._i] = self._cos * v[self._i] + self._sin * v[self._j];
+        r[self._j] = -self._sin * v[self._i] + self._cos * v[self._j];
+        r
+    }
+
+    fn hit(&self, ray: Ray, ray_t: Interval) -> Option<HitRecord>
+    {
+        let rotated = Ray::with_time(self.to_object(ray.origin()), self.to_object(ray.direction()), ray.time());
+
+        let mut rec = self._object.hit(rotated, ray_t)?;
+        rec._point = self.to_world(rec._point);
+        rec._normal = self.to_world(rec._normal);
+        Some(rec)
+    }
+
+    // Rotations keep solid angles, light sampling only needs the frame changed
+    fn pdf_value(&self, origin: Vector3<f32>, direction: Vector3<f32>) -> f32
+    {
+        self._object.pdf_value(self.to_object(origin), self.to_object(direction))
+    }
+
+    fn random(&self, origin: Vector3<f32>) -> Vector3<f32>
+    {
+        self.to_world(self._object.random(self.to_object(origin)))
+    }
+}
+
+// Rotation by `angle` degrees, counter-clockwise looking down the axis towards the origin
+pub struct RotateX(AxisRotation);
+pub struct RotateY(AxisRotation);
+pub struct RotateZ(AxisRotation);
+
+impl RotateX
+{
+    pub fn new(object: Arc<dyn Hittable>, angle: f32) -> Self
+    {
+        RotateX(AxisRotation::new(object, 0, angle))
+    }
+}
+
+impl RotateY
+{
+    pub fn new(object: Arc<dyn Hittable>, angle: f32) -> Self
+    {
+        RotateY(AxisRotation::new(object, 1, angle))
+    }
+}
+
+impl RotateZ
+{
+    pub fn new(object: Arc<dyn Hittable>, angle: f32) -> Self
+    {
+        RotateZ(AxisRotation::new(object, 2, angle))
+    }
+}
+
+impl Hittable for RotateX
+{
+    fn hit(&self, ray: Ray, ray_t: Interval) -> Option<HitRecord>
+    {
+        self.0.hit(ray, ray_t)
+    }
+
+    fn bounding_box(&self) -> Aabb
+    {
+        self.0._bbox
+    }
+
+    fn pdf_value(&self, origin: Vector3<f32>, direction: Vector3<f32>) -> f32
+    {
+        self.0.pdf_value(origin, direction)
+    }
+
+    fn random(&self, origin: Vector3<f32>) -> Vector3<f32>
+    {
+        self.0.random(origin)
+    }
+}
+
+impl Hittable for RotateY
+{
+    fn hit(&self, ray: Ray, ray_t: Interval) -> Option<HitRecord>
+    {
+        self.0.hit(ray, ray_t)
+    }
+
+    fn bounding_box(&self) -> Aabb
+    {
+        self.0._bbox
+    }
+
+    fn pdf_value(&self, origin: Vector3<f32>, direction: Vector3<f32>) -> f32
+    {
+        self.0.pdf_value(origin, direction)
+    }
+
+    fn random(&self, origin: Vector3<f32>) -> Vector3<f32>
+    {
+        self.0.random(origin)
+    }
+}
+
+impl Hittable for RotateZ
+{
+    fn hit(&self, ray: Ray, ray_t: Interval) -> Option<HitRecord>
+    {
+        self.0.hit(ray, ray_t)
+    }
+
+    fn bounding_box(&self) -> Aabb
+    {
+        self.0._bbox
+    }
+
+    fn pdf_value(&self, origin: Vector3<f32>, direction: Vector3<f32>) -> f32
+    {
+        self.0.pdf_value(origin, direction)
+    }
+
+    fn random(&self, origin: Vector3<f32>) -> Vector3<f32>
+    {
+        self.0.random(origin)
+    }
+}
+
+// General affine placement. The inverse and the normal matrix are computed once
+// here, not per ray. Ray directions are not renormalised so hit distances stay
+// valid in world space.
+pub struct Transform
+{
+    _object: Arc<dyn Hittable>,
+    _matrix: Matrix4<f32>,
+    _inverse: Matrix4<f32>,
+    // Inverse transpose, maps normals so they stay perpendicular under non-uniform scale
+    _normal_matrix: Matrix4<f32>,
+    _bbox: Aabb
+}
+
+impl Transform
+{
+    // `None` when the matrix is singular, e.g. a zero scale
+    pub fn new(object: Arc<dyn Hittable>, matrix: Matrix4<f32>) -> Option<Self>
+    {
+        let inverse = matrix.invert()?;
+        let bbox = transform_box(&object.bounding_box(), |p| (matrix * p.extend(1.0)).truncate());
+        Some(Transform
+        {
+            _object: object,
+            _matrix: matrix,
+            _inverse: inverse,
+            _normal_matrix: inverse.transpose(),
+            _bbox: bbox
+        })
+    }
+
+    // Scale, then rotate around x, y and z (degrees), then translate
+    pub fn from_parts(object: Arc<dyn Hittable>, translate: Vector3<f32>, rotate: Vector3<f32>, scale: Vector3<f32>) -> Option<Self>
+    {
+        let matrix = Matrix4::from_translation(translate)
+            * Matrix4::from_angle_z(Deg(rotate.z))
+            * Matrix4::from_angle_y(Deg(rotate.y))
+            * Matrix4::from_angle_x(Deg(rotate.x))
+            * Matrix4::from_nonuniform_scale(scale.x, scale.y, scale.z);
+        Transform::new(object, matrix)
+    }
+
+    pub fn matrix(&self) -> Matrix4<f32>
+    {
+        self._matrix
+    }
+}
+
+impl Hittable for Transform
+{
+    fn hit(&self, ray: Ray, ray_t: Interval) -> Option<HitRecord>
+    {
+        let origin = (self._inverse * ray.origin().extend(1.0)).truncate();
+        let direction = (self._inverse * ray.direction().extend(0.0)).truncate();
+
+        let mut rec = self._object.hit(Ray::with_time(origin, direction, ray.time()), ray_t)?;
+        rec._point = (self._matrix * rec._point.extend(1.0)).truncate();
+        rec._normal = unit_vector3((self._normal_matrix * rec._normal.extend(0.0)).truncate());
+        Some(rec)
+    }
+
+    fn bounding_box(&self) -> Aabb
+    {
+        self._bbox
+    }
+
+    // The object's density is per local solid angle. A linear map A takes the unit
+    // direction d to A d / |A d|, which stretches solid angle by |det A| / |A d|^3.
+    fn pdf_value(&self, origin: Vector3<f32>, direction: Vector3<f32>) -> f32
+    {
+        let origin = (self._inverse * origin.extend(1.0)).truncate();
+        let local = (self._inverse * unit_vector3(direction).extend(0.0)).truncate();
+        let stretch = length(local);
+        self._object.pdf_value(origin, local) * self._inverse.determinant().abs() / (stretch * stretch * stretch)
+    }
+
+    fn random(&self, origin: Vector3<f32>) -> Vector3<f32>
+    {
+        let origin = (self._inverse * origin.extend(1.0)).truncate();
+        (self._matrix * self._object.random(origin).extend(0.0)).truncate()
+    }
+}
+
+#[cfg(test)]
+mod test
+{
+    use cgmath::{Array, InnerSpace};
+    use super::*;
+    use crate::renderer::material::Lambertian;
+    use crate::renderer::sphere::Sphere;
+
+    fn unit_sphere() -> Arc<dyn Hittable>
+    {
+        Arc::new(Sphere::new(Vector3::new(0.0, 0.0, 0.0), 1.0, Arc::new(Lambertian::new(Vector3::from_value(0.5)))))
+    }
+
+    fn hit(object: &dyn Hittable, origin: Vector3<f32>, direction: Vector3<f32>) -> Option<HitRecord>
+    {
+        object.hit(Ray::new(origin, direction), Interval::new(0.001, f32::INFINITY))
+    }
+
+    #[test]
+    fn test_translate_and_rotate_match_transform()
+    {
+        // A quarter turn around y carries the sphere from +z over to +x
+        let sphere = Arc::new(Translate::new(unit_sphere(), Vector3::new(0.0, 0.0, 3.0)));
+        let rotated = RotateY::new(sphere.clone(), 90.0);
+        let general = Transform::from_parts(sphere, Vector3::from_value(0.0), Vector3::new(0.0, 90.0, 0.0), Vector3::from_value(1.0)).unwrap();
+
+        for object in &[&rotated as &dyn Hittable, &general as &dyn Hittable]
+        {
+            let rec = hit(*object, Vector3::new(10.0, 0.0, 0.0), Vector3::new(-1.0, 0.0, 0.0)).unwrap();
+            assert!((rec.get_point() - Vector3::new(4.0, 0.0, 0.0)).magnitude() < 1e-4);
+            assert!((rec.get_normal() - Vector3::new(1.0, 0.0, 0.0)).magnitude() < 1e-4);
+            assert!((object.bounding_box().centroid() - Vector3::new(3.0, 0.0, 0.0)).magnitude() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn test_non_uniform_scale()
+    {
+        let sphere = unit_sphere();
+        let ellipsoid = Transform::new(sphere.clone(), Matrix4::from_nonuniform_scale(2.0, 1.0, 1.0)).unwrap();
+
+        let rec = hit(&ellipsoid, Vector3::new(5.0, 0.0, 0.0), Vector3::new(-1.0, 0.0, 0.0)).unwrap();
+        assert!((rec.get_t() - 3.0).abs() < 1e-4);
+        assert!((rec.get_normal() - Vector3::new(1.0, 0.0, 0.0)).magnitude() < 1e-4);
+        assert!((ellipsoid.bounding_box().max().x - 2.0).abs() < 1e-4);
+
+        assert!(Transform::new(sphere, Matrix4::from_scale(0.0)).is_none());
+    }
+
+    #[test]
+    fn test_placed_lights_keep_their_density()
+    {
+        use crate::renderer::custom_function::random_unit_vector3;
+        use crate::renderer::sampler::seed_thread_sampler;
+        use crate::utility::constants::PI;
+        seed_thread_sampler(7, 0, 0);
+
+        // Rotated or uniformly scaled, a sphere is just a sphere somewhere else
+        let origin = Vector3::new(1.0, 0.5, -0.5);
+        let rotated = RotateY::new(Arc::new(Translate::new(unit_sphere(), Vector3::new(0.0, 0.0, 3.0))), 90.0);
+        let scaled = Transform::from_parts(unit_sphere(), Vector3::new(3.0, 0.0, 0.0), Vector3::new(0.0, 30.0, 0.0), Vector3::from_value(1.5)).unwrap();
+        let rotated_sphere = Sphere::new(Vector3::new(3.0, 0.0, 0.0), 1.0, Arc::new(Lambertian::new(Vector3::from_value(0.5))));
+        let scaled_sphere = Sphere::new(Vector3::new(3.0, 0.0, 0.0), 1.5, Arc::new(Lambertian::new(Vector3::from_value(0.5))));
+        for (placed, sphere) in &[(&rotated as &dyn Hittable, &rotated_sphere), (&scaled as &dyn Hittable, &scaled_sphere)]
+        {
+            for _ in 0..100
+            {
+                let direction = placed.random(origin);
+                assert!(sphere.pdf_value(origin, direction) > 0.0);
+                assert!((placed.pdf_value(origin, direction) / sphere.pdf_value(origin, direction) - 1.0).abs() < 1e-3);
+            }
+        }
+
+        // Squashed into an ellipsoid the density still integrates to one over all directions
+        let ellipsoid = Transform::new(Arc::new(Translate::new(unit_sphere(), Vector3::new(0.0, 0.0, 1.5))), Matrix4::from_nonuniform_scale(2.0, 1.0, 0.5)).unwrap();
+        let samples = 200_000;
+        let total: f32 = (0..samples).map(|_| ellipsoid.pdf_value(Vector3::from_value(0.0), random_unit_vector3())).sum();
+        let integral = total * 4.0 * PI / samples as f32;
+        assert!((integral - 1.0).abs() < 0.03, "{}", integral);
+    }
+}
@@ -0,0 +1,175 @@
+use std::sync::Arc;
+use cgmath::{dot, Vector3};
+use crate::renderer::aabb::Aabb;
+use crate::renderer::custom_function::{length, length_squared, random_double, set_face_normal, unit_vector3};
+use crate::renderer::hittable::{HitRecord, Hittable};
+use crate::renderer::interval::Interval;
+use crate::renderer::material::Material;
+use crate::renderer::ray::Ray;
+
+// Möller–Trumbore ray/triangle intersection.
+// Returns the ray parameter and the barycentric coordinates (b1, b2) of the hit,
+// the hit point being (1 - b1 - b2) * p0 + b1 * p1 + b2 * p2.
+pub fn intersect_triangle(ray: &Ray, ray_t: &Interval, p0: Vector3<f32>, p1: Vector3<f32>, p2: Vector3<f32>) -> Option<(f32, f32, f32)>
+{
+    let edge1 = p1 - p0;
+    let edge2 = p2 - p0;
+
+    let pvec = ray.direction().cross(edge2);
+    let det = dot(edge1, pvec);
+    if det.abs() < 1e-8
+    {
+        // Ray parallel to the triangle plane
+        return None;
+    }
+    let inv_det = 1.0 / det;
+
+    let tvec = ray.origin() - p0;
+    let b1 = dot(tvec, pvec) * inv_det;
+    if !(0.0..=1.0).contains(&b1)
+    {
+        return None;
+    }
+
+    let qvec = tvec.cross(edge1);
+    let b2 = dot(ray.direction(), qvec) * inv_det;
+    if b2 < 0.0 || b1 + b2 > 1.0
+    {
+        return None;
+    }
+
+    let t = dot(edge2, qvec) * inv_det;
+    if !ray_t.surrounds(t)
+    {
+        return None;
+    }
+
+    Some((t, b1, b2))
+}
+
+// Builds the hit record for a triangle hit. The front face is decided by the
+// geometric normal, the (optional) interpolated normal is only used for shading.
+pub fn triangle_hit_record(ray: Ray, t: f32, geometric_normal: Vector3<f32>, shading_normal: Option<Vector3<f32>>, uv: (f32, f32), material: Arc<dyn Material>) -> HitRecord
+{
+    let (normal, front_face) = set_face_normal(ray, geometric_normal);
+    let normal = match shading_normal
+    {
+        Some(n) if front_face => n,
+        Some(n) => -n,
+        None => normal
+    };
+
+    HitRecord{ _t: t, _point: ray.at(t), _normal: normal, _material: material, _u: uv.0, _v: uv.1, _front_face: front_face }
+}
+
+pub fn interpolate(b1: f32, b2: f32, a0: Vector3<f32>, a1: Vector3<f32>, a2: Vector3<f32>) -> Vector3<f32>
+{
+    (1.0 - b1 - b2) * a0 + b1 * a1 + b2 * a2
+}
+
+// Point uniformly distributed over the triangle
+pub fn random_on_triangle(p0: Vector3<f32>, p1: Vector3<f32>, p2: Vector3<f32>) -> Vector3<f32>
+{
+    let r1 = random_double().sqrt();
+    let r2 = random_double();
+    interpolate(r1 * (1.0 - r2), r1 * r2, p0, p1, p2)
+}
+
+pub struct Triangle
+{
+    _vertices: [Vector3<f32>; 3],
+    _normals: Option<[Vector3<f32>; 3]>,
+    _normal: Vector3<f32>,
+    _area: f32,
+    _material: Arc<dyn Material>,
+    _bbox: Aabb
+}
+
+impl Triangle
+{
+    pub fn new(p0: Vector3<f32>, p1: Vector3<f32>, p2: Vector3<f32>, material: Arc<dyn Material>) -> Self
+    {
+        let bbox = Aabb::surrounding(&Aabb::from_points(p0, p1), &Aabb::from_points(p2, p2));
+        Triangle
+        {
+            _vertices: [p0, p1, p2],
+            _normals: None,
+            _normal: unit_vector3((p1 - p0).cross(p2 - p0)),
+            _area: 0.5 * length((p1 - p0).cross(p2 - p0)),
+            _material: material,
+            _bbox: bbox
+        }
+    }
+
+    // Triangle with per-vertex normals, interpolated across the face for smooth shading
+    pub fn with_normals(p0: Vector3<f32>, p1: Vector3<f32>, p2: Vector3<f32>, normals: [Vector3<f32>; 3], material: Arc<dyn Material>) -> Self
+    {
+        let mut triangle = Triangle::new(p0, p1, p2, material);
+        triangle._normals = Some(normals);
+        triangle
+    }
+}
+
+impl Hittable for Triangle
+{
+    fn hit(&self, ray: Ray, ray_t: Interval) -> Option<HitRecord>
+    {
+        let [p0, p1, p2] = self._vertices;
+        let (t, b1, b2) = intersect_triangle(&ray, &ray_t, p0, p1, p2)?;
+
+        let shading_normal = self._normals.map(|[n0, n1, n2]| unit_vector3(interpolate(b1, b2, n0, n1, n2)));
+
+        // Without texture coordinates the barycentrics serve as (u, v)
+        Some(triangle_hit_record(ray, t, self._normal, shading_normal, (b1, b2), self._material.clone()))
+    }
+
+    fn bounding_box(&self) -> Aabb
+    {
+        self._bbox
+    }
+
+    // Uniform over the area, as for `Quad`. The geometric normal is used, not the shading one.
+    fn pdf_value(&self, origin: Vector3<f32>, direction: Vector3<f32>) -> f32
+    {
+        let rec = match self.hit(Ray::new(origin, direction), Interval::new(0.001, f32::INFINITY))
+        {
+            Some(rec) => rec,
+            None => return 0.0
+        };
+
+        let distance_squared = rec._t * rec._t * length_squared(direction);
+        let cosine = (dot(direction, self._normal) / length(direction)).abs();
+        distance_squared / (cosine * self._area)
+    }
+
+    fn random(&self, origin: Vector3<f32>) -> Vector3<f32>
+    {
+        let [p0, p1, p2] = self._vertices;
+        random_on_triangle(p0, p1, p2) - origin
+    }
+}
+
+#[cfg(test)]
+mod test
+{
+    use crate::renderer::material::Lambertian;
+    use super::*;
+
+    #[test]
+    fn test_triangle_hit()
+    {
+        let material = Arc::new(Lambertian::new(Vector3::new(0.5, 0.5, 0.5)));
+        let triangle = Triangle::new(Vector3::new(-1.0, -1.0, 0.0), Vector3::new(1.0, -1.0, 0.0), Vector3::new(0.0, 1.0, 0.0), material);
+
+        let hit = triangle.hit(Ray::new(Vector3::new(0.0, 0.0, 2.0), Vector3::new(0.0, 0.0, -1.0)), Interval::new(0.001, f32::INFINITY)).unwrap();
+        assert!((hit._t - 2.0).abs() < 1e-6);
+        assert!(hit._front_face);
+        assert_eq!(hit._normal, Vector3::new(0.0, 0.0, 1.0));
+
+        let back = triangle.hit(Ray::new(Vector3::new(0.0, 0.0, -2.0), Vector3::new(0.0, 0.0, 1.0)), Interval::new(0.001, f32::INFINITY)).unwrap();
+        assert!(!back._front_face);
+        assert_eq!(back._normal, Vector3::new(0.0, 0.0, -1.0));
+
+        assert!(triangle.hit(Ray::new(Vector3::new(2.0, 0.0, 2.0), Vector3::new(0.0, 0.0, -1.0)), Interval::new(0.001, f32::INFINITY)).is_none());
+    }
+}
@@ -32,6 +32,14 @@ impl DeviceExtension {
 pub const PI: f32 = std::f32::consts::PI;
 pub const INFINITY:f32 = f32::INFINITY;
 
+// Defaults for RenderSettings, overridable from the scene file or command line
 pub const SAMPLES_PER_PIXEL: u32 = 15;
 
-pub const MAX_DEPTH: u32 = 50;
\ No newline at end of file
+pub const MAX_DEPTH: u32 = 50;
+// Bounces every path survives before Russian roulette may end it
+pub const ROULETTE_DEPTH: u32 = 3;
+
+// Edge length in pixels of one render job
+pub const TILE_SIZE: u32 = 32;
+// Samples added to every pixel per progressive pass
+pub const SAMPLES_PER_PASS: u32 = 1;
\ No newline at end of file
@@ -758,9 +758,9 @@ pub fn load_model(model_path: &Path) -> (Vec<VertexV3>, Vec<u32>) {
     for m in models.iter() {
         let mesh = &m.mesh;
 
-        if mesh.texcoords.len() == 0 {
-            panic!("Missing texture coordinate for the model.")
-        }
+        // indices of every model are relative to its own vertices
+        let base_index = vertices.len() as u32;
+        let has_texcoords = mesh.texcoords.len() == mesh.positions.len() / 3 * 2;
 
         let total_vertices_count = mesh.positions.len() / 3;
         for i in 0..total_vertices_count {
@@ -772,12 +772,16 @@ pub fn load_model(model_path: &Path) -> (Vec<VertexV3>, Vec<u32>) {
                     1.0,
                 ],
                 color: [1.0, 1.0, 1.0, 1.0],
-                tex_coord: [mesh.texcoords[i * 2], mesh.texcoords[i * 2 + 1]],
+                tex_coord: if has_texcoords {
+                    [mesh.texcoords[i * 2], mesh.texcoords[i * 2 + 1]]
+                } else {
+                    [0.0, 0.0]
+                },
             };
             vertices.push(vertex);
         }
 
-        indices = mesh.indices.clone();
+        indices.extend(mesh.indices.iter().map(|i| i + base_index));
     }
 
     (vertices, indices)
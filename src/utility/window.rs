@@ -1,4 +1,5 @@
 use cgmath::Vector4;
+use crate::renderer::film::Film;
 use winit::event::{Event, VirtualKeyCode, ElementState, KeyboardInput, WindowEvent};
 use winit::event_loop::{EventLoop, ControlFlow};
 use std::sync::{Mutex, Arc};
@@ -41,7 +42,7 @@ impl ProgramProc {
         ProgramProc { event_loop }
     }
 
-    pub fn main_loop<A: 'static + VulkanApp>(self, mut vulkan_app: A, render_data: Arc<Mutex<Vec<Vector4<f32>>>>) {
+    pub fn main_loop<A: 'static + VulkanApp>(self, mut vulkan_app: A, film: Arc<Mutex<Film>>) {
 
         let mut tick_counter = super::fps_limiter::FPSLimiter::new();
 
@@ -79,7 +80,8 @@ impl ProgramProc {
                 },
                 | Event::RedrawRequested(_window_id) => {
                     let delta_time = tick_counter.delta_time();
-                    let rd = render_data.lock().unwrap().clone().into_boxed_slice().into_vec();
+                    // Show the current average, the film keeps refining in the background
+                    let rd = film.lock().unwrap().resolve();
                     vulkan_app.draw_frame(delta_time, rd);
 
                     if IS_PAINT_FPS_COUNTER {